@@ -0,0 +1,301 @@
+// Benchmark of the guest methods.
+//
+// Proves every guest method over a set of representative boards and records user
+// cycles, segments and proving time in a JSON report. Exits with an error if any guest
+// uses more cycles than the baseline allows, bench/baseline.json unless another one is
+// given.
+//
+//     cargo run -p bench -- [--out bench_report.json] [--baseline bench/baseline.json] [--threshold 0.05]
+//
+// Run with RISC0_DEV_MODE=1 to measure cycles quickly without real proofs. A baseline
+// is just an earlier report kept around: refresh it with `--out bench/baseline.json
+// --no-baseline` when a change to a guest is meant to cost more cycles.
+
+use fleetcore::{
+    move_ship, seeded_board, BaseInputs, BaseJournal, BatchReportInputs, BoardHistory, Command, Direction, FireInputs, MoveInputs,
+    OwnershipInputs, ReportInputs, ScanArea, ScanInputs, ScanReportInputs, SCAN_SIZE,
+};
+use methods::{
+    BATCH_REPORT_ELF, BATCH_REPORT_ID, FIRE_ELF, JOIN_ELF, JOIN_ID, MOVE_SHIP_ELF, MOVE_SHIP_ID, OWNERSHIP_ELF,
+    REPORT_ELF, REPORT_ID, SCAN_ELF, SCAN_REPORT_ELF, WAVE_ELF, WIN_ELF,
+};
+use risc0_zkvm::{default_prover, ExecutorEnv, Receipt};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// Measurements for one guest run on one board
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct BenchResult {
+    guest: String,
+    board: String,
+    user_cycles: u64,
+    total_cycles: u64,
+    segments: usize,
+    prove_ms: u128,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BenchReport {
+    results: Vec<BenchResult>,
+}
+
+// Report committed next to the benchmark, compared against by default
+const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/baseline.json");
+
+struct Options {
+    out: String,
+    // None with --no-baseline, to write a fresh baseline
+    baseline: Option<String>,
+    // Allowed relative increase of user cycles over the baseline
+    threshold: f64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        out: "bench_report.json".to_string(),
+        baseline: Some(BASELINE.to_string()),
+        threshold: 0.05,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--no-baseline" {
+            options.baseline = None;
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--out" => options.out = value,
+            "--baseline" => options.baseline = Some(value),
+            "--threshold" => {
+                options.threshold = value
+                    .parse()
+                    .map_err(|_| format!("Invalid threshold {}", value))?
+            }
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+// Boards the guests are measured on: a hand-made one with every ship packed
+// against the left edge and a few generated ones
+fn boards() -> Vec<(String, Vec<u8>)> {
+    let mut boards = vec![(
+        "stacked".to_string(),
+        vec![0, 1, 2, 3, 4, 9, 20, 21, 22, 23, 40, 41, 42, 60, 61, 80, 81, 99],
+    )];
+    for seed in 1..=3 {
+        boards.push((format!("seed-{}", seed), seeded_board(seed)));
+    }
+    boards
+}
+
+fn prove<T: Serialize>(
+    guest: &str,
+    board: &str,
+    input: &T,
+    assumptions: &[Receipt],
+    elf: &[u8],
+) -> (BenchResult, Receipt) {
+    let mut builder = ExecutorEnv::builder();
+    builder.write(input).unwrap();
+    for receipt in assumptions {
+        builder.add_assumption(receipt.clone());
+    }
+    let env = builder.build().unwrap();
+
+    let start = Instant::now();
+    let info = default_prover()
+        .prove(env, elf)
+        .unwrap_or_else(|err| panic!("{} failed on board {}: {}", guest, board, err));
+    let prove_ms = start.elapsed().as_millis();
+
+    let result = BenchResult {
+        guest: guest.to_string(),
+        board: board.to_string(),
+        user_cycles: info.stats.user_cycles,
+        total_cycles: info.stats.total_cycles,
+        segments: info.stats.segments,
+        prove_ms,
+    };
+    println!(
+        "{:<8} {:<10} {:>10} user cycles {:>10} total cycles {:>3} segments {:>8} ms",
+        result.guest, result.board, result.user_cycles, result.total_cycles, result.segments, result.prove_ms
+    );
+    (result, info.receipt)
+}
+
+fn bench_board(name: &str, board: &[u8]) -> Vec<BenchResult> {
+    let base = BaseInputs {
+        gameid: "bench".to_string(),
+        fleet: "bench".to_string(),
+        board: board.to_vec(),
+        random: "benchrandom1".to_string(),
+        team: None,
+        mines: Vec::new(),
+    };
+    let mut results = Vec::new();
+
+    let (result, join_receipt) = prove("join", name, &base, &[], JOIN_ELF);
+    results.push(result);
+    let join: BaseJournal = join_receipt.journal.decode().unwrap();
+
+    let fire = FireInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        target: "target".to_string(),
+        positions: vec![55],
+        origin_board: base.board.clone(),
+    };
+    results.push(prove("fire", name, &fire, &[], FIRE_ELF).0);
+
+    // A hit on the first ship cell, so the board gets updated too
+    let report = ReportInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        report: "Hit".to_string(),
+        pos: board[0],
+        mines: Vec::new(),
+        history: BoardHistory::Joined(join.clone()),
+        join_id: JOIN_ID.into(),
+        report_id: REPORT_ID.into(),
+        move_id: MOVE_SHIP_ID.into(),
+    };
+    results.push(prove("report", name, &report, std::slice::from_ref(&join_receipt), REPORT_ELF).0);
+
+    // The first ship that has room to move by one cell
+    let directions = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+    let movable = board
+        .iter()
+        .flat_map(|&pos| directions.map(|direction| (pos, direction)))
+        .find(|&(pos, direction)| move_ship(board, &[], &[], pos, direction).is_ok());
+    if let Some((ship, direction)) = movable {
+        let moved = MoveInputs {
+            gameid: base.gameid.clone(),
+            fleet: base.fleet.clone(),
+            board: base.board.clone(),
+            random: base.random.clone(),
+            hits: Vec::new(),
+            ship,
+            direction,
+            mines: Vec::new(),
+            history: BoardHistory::Joined(join.clone()),
+            join_id: JOIN_ID.into(),
+            report_id: REPORT_ID.into(),
+            move_id: MOVE_SHIP_ID.into(),
+        };
+        results.push(prove("move", name, &moved, std::slice::from_ref(&join_receipt), MOVE_SHIP_ELF).0);
+    }
+
+    // A salvo of five shots on ship cells, so every one is a hit
+    let batch = BatchReportInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        positions: board.iter().step_by(2).take(5).copied().collect(),
+        history: BoardHistory::Joined(join),
+        join_id: JOIN_ID.into(),
+        batch_report_id: BATCH_REPORT_ID.into(),
+    };
+    results.push(prove("batch", name, &batch, &[join_receipt], BATCH_REPORT_ELF).0);
+
+    // A scan of the top left corner, where the stacked board has ships
+    let area = ScanArea { pos: 0, size: SCAN_SIZE };
+    let scan = ScanInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        target: "target".to_string(),
+        area,
+    };
+    results.push(prove("scan", name, &scan, &[], SCAN_ELF).0);
+    let scan_report = ScanReportInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        area,
+    };
+    results.push(prove("scan_report", name, &scan_report, &[], SCAN_REPORT_ELF).0);
+
+    results.push(prove("wave", name, &base, &[], WAVE_ELF).0);
+    results.push(prove("win", name, &base, &[], WIN_ELF).0);
+    let ownership = OwnershipInputs {
+        gameid: base.gameid.clone(),
+        fleet: base.fleet.clone(),
+        board: base.board.clone(),
+        random: base.random.clone(),
+        action: Command::OfferDraw,
+        offer: 1,
+    };
+    results.push(prove("ownership", name, &ownership, &[], OWNERSHIP_ELF).0);
+    results
+}
+
+// Lists every guest whose user cycles grew past the threshold
+fn regressions(report: &BenchReport, baseline: &BenchReport, threshold: f64) -> Vec<String> {
+    let mut failures = Vec::new();
+    for result in &report.results {
+        let previous = baseline
+            .results
+            .iter()
+            .find(|b| b.guest == result.guest && b.board == result.board);
+        if let Some(previous) = previous {
+            let limit = previous.user_cycles as f64 * (1.0 + threshold);
+            if result.user_cycles as f64 > limit {
+                failures.push(format!(
+                    "{} on board {}: {} user cycles, baseline {}",
+                    result.guest, result.board, result.user_cycles, previous.user_cycles
+                ));
+            }
+        }
+    }
+    failures
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("Usage: bench [--out file] [--baseline file | --no-baseline] [--threshold fraction]");
+            std::process::exit(2);
+        }
+    };
+    // Read before proving, which takes a while
+    let baseline = options.baseline.as_ref().map(|path| {
+        let json = std::fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("Could not read the baseline {}: {}", path, err);
+            eprintln!("Write one with --out {} --no-baseline", BASELINE);
+            std::process::exit(2);
+        });
+        serde_json::from_str::<BenchReport>(&json).expect("Invalid baseline")
+    });
+
+    let mut report = BenchReport { results: Vec::new() };
+    for (name, board) in boards() {
+        report.results.extend(bench_board(&name, &board));
+    }
+
+    let json = serde_json::to_string_pretty(&report).unwrap();
+    std::fs::write(&options.out, json).expect("Could not write the benchmark report");
+    println!("Report written to {}", options.out);
+
+    if let (Some(path), Some(baseline)) = (&options.baseline, baseline) {
+        let failures = regressions(&report, &baseline, options.threshold);
+        if !failures.is_empty() {
+            eprintln!("Cycle count regressions over {:.0}%:", options.threshold * 100.0);
+            for failure in failures {
+                eprintln!("  {}", failure);
+            }
+            std::process::exit(1);
+        }
+        println!("No regressions against {}", path);
+    }
+}
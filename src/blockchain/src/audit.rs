@@ -0,0 +1,109 @@
+// Checks a game transcript exported with its receipts, without trusting the node that
+// exported it. Every receipt is verified against the image IDs this build expects,
+// then the moves are applied in order on a node of its own, seated like the tournament
+// match if the game is one, which must accept each one and publish the same events,
+// down to the same teams and result.
+
+use crate::ledger::{Entry, Transaction};
+use crate::transcript::{journal_json, Transcript};
+use crate::{apply_entry, DevPolicy, Game, SharedData};
+use fleetcore::CommunicationData;
+
+// Describes the game that checked out, or the first inconsistency found
+pub fn audit(transcript: &Transcript) -> Result<String, String> {
+    let shared = SharedData::open(
+        if transcript.dev { DevPolicy::DevGames } else { DevPolicy::Reject },
+        None,
+    )?;
+    let Some(methods) = shared.methods.iter().find(|methods| methods.protocol == transcript.protocol).cloned() else {
+        return Err(format!("Protocol {} is not accepted by this build", transcript.protocol));
+    };
+
+    // Set up like the node did, a tournament game was created with its seats before
+    // anyone joined it, and is a dev game only in a dev tournament
+    let mut game = Game::new(transcript.mode);
+    if transcript.tournament.is_some() {
+        game.tournament = transcript.tournament.clone();
+        game.seats = transcript.seats.clone();
+        game.dev = transcript.dev;
+    }
+    shared.gmap.lock().unwrap().insert(transcript.gameid.clone(), game);
+    shared.game_logs.lock().unwrap().insert(transcript.gameid.clone(), Vec::new());
+
+    for (i, played) in transcript.moves.iter().enumerate() {
+        let inconsistent = |what: String| format!("Move {} ({:?} by {}): {}", i + 1, played.cmd, played.fleet, what);
+        let Some(receipt) = &played.receipt else {
+            return Err(inconsistent("no receipt, export the transcript with ?receipts=true".to_string()));
+        };
+        let expected = methods.image_id(played.cmd);
+        if played.image_id != expected {
+            return Err(inconsistent(format!("image ID {} where {} was expected", played.image_id, expected)));
+        }
+        if !transcript.dev && receipt.verify(expected).is_err() {
+            return Err(inconsistent(format!("receipt does not verify against image ID {}", expected)));
+        }
+
+        let data = CommunicationData { cmd: played.cmd, receipt: receipt.clone(), mode: played.mode };
+        let journal = journal_json(&data);
+        if journal.as_ref() != Some(&played.journal) {
+            return Err(inconsistent("journal does not match the receipt".to_string()));
+        }
+        if journal.as_ref().and_then(|journal| journal["gameid"].as_str()) != Some(transcript.gameid.as_str()) {
+            return Err(inconsistent(format!("not a move of game {}", transcript.gameid)));
+        }
+
+        let mut entry = Entry { time: played.time, transaction: Transaction::Move(data), events: Vec::new() };
+        let response = apply_entry(&shared, &mut entry);
+        if response != "OK" {
+            return Err(inconsistent(format!("rejected: {}", response)));
+        }
+        if entry.events != played.events {
+            return Err(inconsistent(format!(
+                "published \"{}\" where the transcript has \"{}\"",
+                entry.events.join("; "),
+                played.events.join("; ")
+            )));
+        }
+    }
+
+    let (winner, drawn, teams) = {
+        let gmap = shared.gmap.lock().unwrap();
+        let game = &gmap[&transcript.gameid];
+        (game.winner.clone(), game.drawn, game.team_fleets())
+    };
+    if teams != transcript.teams {
+        return Err(format!(
+            "Game {} was played by teams {:?}, the transcript says {:?}",
+            transcript.gameid, teams, transcript.teams
+        ));
+    }
+    if drawn != transcript.drawn {
+        return Err(format!(
+            "Game {} {} in a draw, the transcript says it {}",
+            transcript.gameid,
+            if drawn { "ended" } else { "did not end" },
+            if transcript.drawn { "did" } else { "did not" }
+        ));
+    }
+    if winner != transcript.winner {
+        return Err(format!(
+            "Game {} was won by {}, the transcript says {}",
+            transcript.gameid,
+            winner.as_deref().unwrap_or("nobody"),
+            transcript.winner.as_deref().unwrap_or("nobody")
+        ));
+    }
+    let mut summary = format!("Game {} checks out: {} moves", transcript.gameid, transcript.moves.len());
+    if let Some(tournament) = &transcript.tournament {
+        summary.push_str(&format!(" in tournament {}", tournament));
+    }
+    match &winner {
+        Some(winner) => summary.push_str(&format!(", won by {}", winner)),
+        None if drawn => summary.push_str(", drawn"),
+        None => summary.push_str(", not over yet"),
+    }
+    if transcript.dev {
+        summary.push_str(". It is a dev game, its fake receipts prove nothing");
+    }
+    Ok(summary)
+}
@@ -0,0 +1,41 @@
+// Checks a game transcript without trusting the node it came from:
+//
+//   curl "$CHAIN_URL/games/g1/transcript?receipts=true" > g1.json
+//   cargo run --bin audit -- g1.json
+//
+// Run it with the node's SCAN_SIZE, SCANS_PER_GAME and ACCEPTED_METHODS, if set, since
+// they change which moves the node accepts. A dev game fails the audit, since its fake
+// receipts prove nothing, unless --allow-dev is passed.
+
+use blockchain::{audit, Transcript};
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let allow_dev = args.iter().any(|arg| arg == "--allow-dev");
+    let Some(path) = args.iter().find(|arg| *arg != "--allow-dev") else {
+        eprintln!("Usage: audit [--allow-dev] <transcript.json>");
+        exit(2);
+    };
+    let json = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", path, e);
+        exit(2);
+    });
+    let transcript: Transcript = serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("Invalid transcript in {}: {}", path, e);
+        exit(2);
+    });
+    match audit(&transcript) {
+        Ok(summary) => {
+            println!("{}", summary);
+            if transcript.dev && !allow_dev {
+                eprintln!("{} is a dev game, pass --allow-dev to accept it", path);
+                exit(1);
+            }
+        }
+        Err(inconsistency) => {
+            eprintln!("{}", inconsistency);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// The node's transaction log, every transaction it accepted in the order it applied them.
+// Kept in the file named by CHAIN_LOG, one JSON transaction per line with the time it
+// was accepted, so a node replays it when started again and games and ratings survive
+// a restart.
+
+use crate::tournament::TournamentCommand;
+use fleetcore::{CommunicationData, RevealData};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum Transaction {
+    // A player's move, posted to /chain with its receipt
+    Move(CommunicationData),
+    Tournament(TournamentCommand),
+    // A fleet's board revealed once its game is over, posted to /reveal
+    Reveal(RevealData),
+}
+
+// A transaction as logged, with when the node accepted it
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Entry {
+    // Milliseconds since the Unix epoch, 0 for a line logged before the node kept the time
+    #[serde(default)]
+    pub(crate) time: u64,
+    #[serde(flatten)]
+    pub(crate) transaction: Transaction,
+    // Messages published about the transaction's game while applying it. Not stored,
+    // a replay publishes them again.
+    #[serde(skip)]
+    pub(crate) events: Vec<String>,
+}
+
+impl Entry {
+    // The transaction, accepted now
+    pub(crate) fn now(transaction: Transaction) -> Self {
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        Entry { time, transaction, events: Vec::new() }
+    }
+}
+
+pub(crate) struct Ledger {
+    entries: Vec<Entry>,
+    // Accepted transactions are appended to it once open
+    file: Option<File>,
+}
+
+impl Ledger {
+    pub(crate) fn new() -> Self {
+        Ledger { entries: Vec::new(), file: None }
+    }
+
+    // Transactions stored in the file, none if it does not exist yet. A last line
+    // without its newline was cut short by a crash and is dropped, and a line that is
+    // no transaction is reported and skipped, so the node still comes back up.
+    pub(crate) fn load(path: &Path) -> Vec<Entry> {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        let mut lines: Vec<&str> = content.split('\n').collect();
+        if lines.pop().is_some_and(|cut| !cut.is_empty()) {
+            println!("Line {} of {} was cut short and is dropped", lines.len() + 1, path.display());
+        }
+        lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    println!("Invalid transaction on line {} of {} skipped: {}", i + 1, path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Stores accepted transactions in the file from now on, after dropping a line cut short
+    pub(crate) fn open(&mut self, path: &Path) {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap_or_else(|e| panic!("Could not open {}: {}", path.display(), e));
+        let content = std::fs::read(path).unwrap_or_default();
+        let complete = content.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        file.set_len(complete as u64).expect("Could not truncate the transaction log");
+        self.file = Some(file);
+    }
+
+    pub(crate) fn append(&mut self, entry: Entry) {
+        if let Some(file) = &mut self.file {
+            let mut line = serde_json::to_string(&entry).unwrap();
+            line.push('\n');
+            file.write_all(line.as_bytes()).expect("Could not write to the transaction log");
+        }
+        self.entries.push(entry);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+}
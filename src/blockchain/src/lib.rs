@@ -0,0 +1,2299 @@
+// Remove the following 3 lines to enable compiler checkings
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::stream::StreamExt;
+use rand::{seq::IteratorRandom, SeedableRng};
+use risc0_zkvm::sha::{self, Digestible};
+use risc0_zkvm::{Digest, InnerReceipt, Receipt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    error::Error,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use fleetcore::{commitment, fleet_cells, on_board, BaseJournal, BatchReportJournal, Command, FireJournal, CommunicationData, GameMode, MethodSet, MoveJournal, OwnershipJournal, ReportJournal, RevealData, ScanArea, ScanJournal, ScanReportJournal, VersionInfo, BOARD_SIDE, FLEET_MINES, PROTOCOL_VERSION, SCAN_SIZE};
+use methods::{BATCH_REPORT_ID, FIRE_ID, JOIN_ID, MOVE_SHIP_ID, OWNERSHIP_ID, REPORT_ID, SCAN_ID, SCAN_REPORT_ID, WAVE_ID, WIN_ID};
+
+mod audit;
+mod ledger;
+mod ratings;
+mod tournament;
+mod transcript;
+
+pub use audit::audit;
+use ledger::{Entry, Ledger, Transaction};
+use ratings::{ratings, GameResult, PlayerRating};
+pub use tournament::{Format, TournamentCommand};
+use tournament::{Match, Tournament, TournamentStanding};
+pub use transcript::{Transcript, TranscriptMove};
+use transcript::{journal_json, move_of};
+
+struct Player {
+    name: String,
+    current_state: Digest,
+    // Board commitment made at join, the root of the fleet's report history
+    origin: Digest,
+    // Number of reports and moves accepted for the fleet
+    reports: u32,
+    // Latest report or move receipt, it attests the fleet's entire history so older ones are dropped
+    history: Option<Receipt>,
+    // Ship cells hit so far, the fleet is eliminated once every one of them is
+    hits: Vec<u8>,
+    // Team declared at join, none in a game without teams
+    team: Option<String>,
+    // Radar scans the fleet used so far
+    scans: u32,
+    // Commitment to the mines placed at join, in a game played with mines
+    mines: Option<Digest>,
+    // Fired on a mine, so the fleet sits out its next turn
+    loses_turn: bool,
+    // Gave up the game, the fleet is out as if sunk
+    resigned: bool,
+    // Shots reported on the fleet with their result, in order
+    reported: Vec<(u8, String)>,
+    // Number of reports made before the fleet first moved a ship, the later ones are
+    // on a layout the board committed at join does not show
+    moved_after: Option<usize>,
+    // Board and mines the fleet revealed once the game was over
+    revealed: Option<Revealed>,
+}
+
+// A fleet's revealed board and mines, with the reports the board contradicts
+#[derive(Clone, Serialize)]
+struct Revealed {
+    board: Vec<String>,
+    mines: Vec<String>,
+    // Reports the board contradicts, e.g. "B3 reported Miss"
+    misreports: Vec<String>,
+    // Reports made after the fleet moved a ship, not checked
+    unchecked: usize,
+}
+
+impl Player {
+    fn eliminated(&self) -> bool {
+        self.resigned || self.hits.len() >= fleet_cells()
+    }
+}
+
+struct Game {
+    pmap: HashMap<String, Player>,
+    // Fleets in the order they joined, turns go round in this order
+    order: Vec<String>,
+    next_player: Option<String>,
+    // Positions of the shots the target has yet to report
+    pending_reports: Vec<u8>,
+    // Area of the radar scan the target has yet to answer
+    pending_scan: Option<ScanArea>,
+    mode: GameMode,
+    // Fleet whose shots wait for a report, the turn passes on from it
+    shooter: Option<String>,
+    // Set by the first shot, no fleet can join after that
+    started: bool,
+    // Last fleet standing, or the member of the last team standing, once it claimed victory
+    winner: Option<String>,
+    // Started with a fake receipt, so fake receipts are accepted for it
+    dev: bool,
+    // Protocol version of the methods the game was started with, it sticks to them
+    protocol: u32,
+    // Fleets drawn to play a tournament match, only they can join. Empty for an open game.
+    seats: Vec<String>,
+    tournament: Option<String>,
+    // Member of each team that last had the turn, the team's next turn goes to the one after
+    team_turns: HashMap<String, String>,
+    // Every shot fired in the game, in order
+    shots: Vec<Shot>,
+    // Fleets that agreed to the draw on offer, the one that offered it first. A shot or
+    // a resignation withdraws the offer.
+    draw: Vec<String>,
+    // Draw offers made in the game so far, the last one is on offer until withdrawn.
+    // Draw receipts name the offer they are about, so none can be replayed on a later one.
+    draw_offers: u32,
+    // Every fleet afloat agreed to a draw, so nobody won
+    drawn: bool,
+}
+
+struct Shot {
+    shooter: String,
+    target: String,
+    pos: u8,
+    // "Hit", "Miss" or "Mine", none until the target reports it
+    result: Option<String>,
+}
+
+impl Game {
+    // A game nobody joined yet, the first fleet to join sets it up
+    fn new(mode: GameMode) -> Self {
+        Game {
+            pmap: HashMap::new(),
+            order: Vec::new(),
+            next_player: None,
+            pending_reports: Vec::new(),
+            pending_scan: None,
+            mode,
+            shooter: None,
+            started: false,
+            winner: None,
+            dev: false,
+            protocol: PROTOCOL_VERSION,
+            seats: Vec::new(),
+            tournament: None,
+            team_turns: HashMap::new(),
+            shots: Vec::new(),
+            draw: Vec::new(),
+            draw_offers: 0,
+            drawn: false,
+        }
+    }
+
+    // Whether the fleet already fired at the position on the target's board. Ships move
+    // in Movable mode, so there only a hit cell is never fired at again.
+    fn fired_at(&self, fleet: &str, target: &str, pos: u8) -> bool {
+        self.shots.iter().any(|shot| {
+            shot.shooter == fleet
+                && shot.target == target
+                && shot.pos == pos
+                && (self.mode != GameMode::Movable || shot.result.as_deref() == Some("Hit"))
+        })
+    }
+
+    // Fills in the results of the shots waiting for their report, in the order fired
+    fn record_results(&mut self, results: &[String]) {
+        let pending = self.shots.iter_mut().filter(|shot| shot.result.is_none());
+        for (shot, result) in pending.zip(results) {
+            shot.result = Some(result.clone());
+        }
+    }
+
+    // The target of the last shots or scan has yet to answer them
+    fn awaiting_answer(&self) -> bool {
+        !self.pending_reports.is_empty() || self.pending_scan.is_some()
+    }
+
+    // Fleets place mines when the first one to join did
+    fn mined(&self) -> bool {
+        self.order.first().is_some_and(|fleet| self.pmap[fleet].mines.is_some())
+    }
+
+    // Fleets declare a team when the first one to join did
+    fn teamed(&self) -> bool {
+        self.order.first().is_some_and(|fleet| self.pmap[fleet].team.is_some())
+    }
+
+    // Teams in the order their first fleet joined
+    fn teams(&self) -> Vec<String> {
+        let mut teams: Vec<String> = Vec::new();
+        for team in self.order.iter().filter_map(|fleet| self.pmap[fleet].team.as_ref()) {
+            if !teams.contains(team) {
+                teams.push(team.clone());
+            }
+        }
+        teams
+    }
+
+    // Fleets of each team, in the order they joined
+    fn team_fleets(&self) -> BTreeMap<String, Vec<String>> {
+        self.teams()
+            .into_iter()
+            .map(|team| {
+                let fleets = self.order.iter().filter(|f| self.pmap[*f].team.as_ref() == Some(&team)).cloned().collect();
+                (team, fleets)
+            })
+            .collect()
+    }
+
+    // A fleet is on its own side, and on its team's
+    fn allies(&self, fleet: &str, other: &str) -> bool {
+        fleet == other || (self.pmap[fleet].team.is_some() && self.pmap[fleet].team == self.pmap[other].team)
+    }
+
+    // Team with the only fleets afloat, in a game played in teams
+    fn last_team_standing(&self) -> Option<String> {
+        let alive = self.alive();
+        let first = alive.first()?;
+        if alive.iter().all(|fleet| self.allies(first, fleet)) {
+            self.pmap[first].team.clone()
+        } else {
+            None
+        }
+    }
+
+    // How the winner is named in messages
+    fn winner_label(&self) -> Option<String> {
+        let winner = self.winner.as_ref()?;
+        Some(match &self.pmap[winner].team {
+            Some(team) => format!("team {}", team),
+            None => format!("player {}", winner),
+        })
+    }
+
+    // Result of the game once it was won or drawn, the fleets afloat share a draw
+    fn result(&self, gameid: &str) -> GameResult {
+        let (winners, losers): (Vec<String>, Vec<String>) = match &self.winner {
+            Some(winner) => self.order.iter().cloned().partition(|fleet| self.allies(winner, fleet)),
+            None => self.order.iter().cloned().partition(|fleet| !self.pmap[fleet].eliminated()),
+        };
+        let mut draws = Vec::new();
+        if self.drawn {
+            for (i, fleet) in winners.iter().enumerate() {
+                for other in winners[i + 1..].iter().filter(|other| !self.allies(fleet, other)) {
+                    draws.push((fleet.clone(), other.clone()));
+                }
+            }
+        }
+        GameResult { gameid: gameid.to_string(), winners, losers, draws }
+    }
+
+    // Why no move is accepted any more, once the game was won or drawn
+    fn over(&self, gameid: &str) -> Option<String> {
+        if self.drawn {
+            return Some(format!("Game {} is over, it ended in a draw", gameid));
+        }
+        Some(format!("Game {} is over, {} won", gameid, self.winner_label()?))
+    }
+
+    // Fleets still afloat, in turn order
+    fn alive(&self) -> Vec<String> {
+        self.order
+            .iter()
+            .filter(|fleet| !self.pmap[*fleet].eliminated())
+            .cloned()
+            .collect()
+    }
+
+    // Fleet whose turn comes after the given one's. A fleet that fired on a mine sits
+    // that turn out and the turn goes on to the fleet after it.
+    fn next_after(&mut self, fleet: &str) -> Option<String> {
+        let next = self.next_seat(fleet)?;
+        if std::mem::take(&mut self.pmap.get_mut(&next)?.loses_turn) {
+            return self.next_after(&next);
+        }
+        Some(next)
+    }
+
+    // First fleet afloat after the given one in turn order, eliminated fleets are skipped.
+    // In a team game the turn goes to the next team with a fleet afloat instead.
+    fn next_seat(&mut self, fleet: &str) -> Option<String> {
+        let Some(team) = self.pmap.get(fleet).and_then(|player| player.team.clone()) else {
+            let start = self.order.iter().position(|f| f == fleet)?;
+            return (1..=self.order.len())
+                .map(|i| &self.order[(start + i) % self.order.len()])
+                .find(|f| !self.pmap[*f].eliminated())
+                .cloned();
+        };
+        self.team_turns.insert(team.clone(), fleet.to_string());
+        let teams = self.teams();
+        let start = teams.iter().position(|t| *t == team)?;
+        (1..=teams.len()).find_map(|i| self.next_in_team(&teams[(start + i) % teams.len()]))
+    }
+
+    // Member of the team afloat that comes after the one that last had the turn
+    fn next_in_team(&self, team: &str) -> Option<String> {
+        let members: Vec<&String> = self
+            .order
+            .iter()
+            .filter(|fleet| self.pmap[*fleet].team.as_deref() == Some(team))
+            .collect();
+        let start = self
+            .team_turns
+            .get(team)
+            .and_then(|last| members.iter().position(|m| *m == last))
+            .map_or(0, |i| i + 1);
+        (0..members.len())
+            .map(|i| members[(start + i) % members.len()])
+            .find(|fleet| !self.pmap[*fleet].eliminated())
+            .cloned()
+    }
+}
+
+// What the node does with the fake receipts a host makes in RISC0_DEV_MODE,
+// set with DEV_RECEIPTS so it does not depend on the node's own RISC0_DEV_MODE
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DevPolicy {
+    // Fake receipts are never accepted
+    Reject,
+    // A game started with a fake receipt is a dev game and accepts them, so it proves nothing
+    DevGames,
+}
+
+impl DevPolicy {
+    pub fn from_env() -> Self {
+        match std::env::var("DEV_RECEIPTS").as_deref() {
+            Ok("dev-games") => DevPolicy::DevGames,
+            _ => DevPolicy::Reject,
+        }
+    }
+}
+
+// Radar scans allowed in a game, set with SCAN_SIZE and SCANS_PER_GAME
+#[derive(Clone, Copy)]
+pub struct ScanRules {
+    // Side of the square a scan covers
+    pub size: u8,
+    // Scans each fleet may use in a game
+    pub per_game: u32,
+}
+
+impl ScanRules {
+    // A scan must fit on the board, so SCAN_SIZE is refused outside 1 to BOARD_SIDE
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().and_then(|value| value.parse().ok());
+        let size = match std::env::var("SCAN_SIZE") {
+            Ok(value) => value
+                .parse::<u8>()
+                .ok()
+                .filter(|size| (1..=BOARD_SIDE).contains(&(*size as usize)))
+                .ok_or_else(|| format!("SCAN_SIZE must be between 1 and {}, not {}", BOARD_SIDE, value))?,
+            Err(_) => SCAN_SIZE,
+        };
+        Ok(ScanRules {
+            size,
+            per_game: var("SCANS_PER_GAME").unwrap_or(2),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedData {
+    tx: broadcast::Sender<String>,
+    // Keeps the channel open so log messages can be sent while nobody is listening
+    _rx: Arc<broadcast::Receiver<String>>,
+    gmap: Arc<Mutex<HashMap<String, Game>>>,
+    // Every message published about a game since it was created, a message's
+    // index is its event id on the game's spectator stream
+    game_logs: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    // Messages published about games by the transaction being applied, they go into
+    // the games' logs only once it is accepted
+    pending: Arc<Mutex<Vec<(String, String)>>>,
+    // Wakes the games' event streams when messages went into their logs
+    logged: broadcast::Sender<()>,
+    rng: Arc<Mutex<rand::rngs::StdRng>>,
+    dev_policy: DevPolicy,
+    // Method sets receipts are verified against, this build's own first
+    methods: Arc<Vec<MethodSet>>,
+    // Accepted transactions, the game state and the ratings follow from them
+    ledger: Arc<Mutex<Ledger>>,
+    tournaments: Arc<Mutex<HashMap<String, Tournament>>>,
+    // Games that ended, in order, the ratings follow from them
+    results: Arc<Mutex<Vec<GameResult>>>,
+    // Dev games that ended, rated apart so test games don't move real ratings
+    dev_results: Arc<Mutex<Vec<GameResult>>>,
+    scan_rules: ScanRules,
+}
+
+impl SharedData {
+    // Keeps the transaction log in the file named by CHAIN_LOG, if set
+    pub fn new(dev_policy: DevPolicy) -> Result<Self, String> {
+        Self::open(dev_policy, std::env::var_os("CHAIN_LOG").map(PathBuf::from))
+    }
+
+    // Replays the transaction log in the file, if any, and appends to it from then on.
+    // Fails when the methods to accept can't be read.
+    pub fn open(dev_policy: DevPolicy, log: Option<PathBuf>) -> Result<Self, String> {
+        // Create a broadcast channel for log messages
+        let (tx, rx) = broadcast::channel::<String>(100);
+        let shared = SharedData {
+            tx,
+            _rx: Arc::new(rx),
+            gmap: Arc::new(Mutex::new(HashMap::new())),
+            game_logs: Arc::new(Mutex::new(HashMap::new())),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            logged: broadcast::channel(16).0,
+            rng: Arc::new(Mutex::new(rand::rngs::StdRng::from_entropy())),
+            dev_policy,
+            methods: Arc::new(accepted_methods()?),
+            ledger: Arc::new(Mutex::new(Ledger::new())),
+            tournaments: Arc::new(Mutex::new(HashMap::new())),
+            results: Arc::new(Mutex::new(Vec::new())),
+            dev_results: Arc::new(Mutex::new(Vec::new())),
+            scan_rules: ScanRules::from_env()?,
+        };
+        if let Some(path) = log {
+            shared.replay(&path);
+        }
+        Ok(shared)
+    }
+
+    // Applies the stored transactions again. One that is refused now, say because its
+    // methods are no longer accepted, is left out along with what depended on it.
+    fn replay(&self, path: &std::path::Path) {
+        let transactions = Ledger::load(path);
+        let total = transactions.len();
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        for (i, mut entry) in transactions.into_iter().enumerate() {
+            let response = apply_entry(self, &mut entry);
+            if response == "OK" {
+                ledger.append(entry);
+            } else {
+                println!("Transaction {} of {} not replayed: {}", i + 1, path.display(), response);
+            }
+        }
+        ledger.open(path);
+        println!("Replayed {} of {} transactions from {}", ledger.len(), total, path.display());
+    }
+
+    // Receives every log message published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.tx.subscribe()
+    }
+
+    // Sends a message to the node's log and keeps it for the game's events, which get it
+    // if the transaction is accepted
+    fn publish(&self, gameid: &str, msg: String) {
+        self.pending.lock().unwrap().push((gameid.to_string(), msg.clone()));
+        let _ = self.tx.send(msg);
+    }
+}
+
+// Builds the node's routes around the shared state
+pub fn app(shared: SharedData) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/logs", get(logs))
+        .route("/chain", post(smart_contract))
+        .route("/reveal", post(reveal_fleet))
+        .route("/games/:id", get(game_state))
+        .route("/games/:id/events", get(game_events))
+        .route("/games/:id/watch", get(watch))
+        .route("/games/:id/transcript", get(game_transcript))
+        .route("/games/:id/replay", get(replay))
+        .route("/leaderboard", get(leaderboard))
+        .route("/players/:fleet", get(player_rating))
+        .route("/tournaments", post(tournament_action))
+        .route("/tournaments/:id", get(tournament_state))
+        .route("/tournaments/:id/standings", get(tournament_standings))
+        .route("/version", get(version))
+        .layer(Extension(shared))
+}
+
+// Game state as returned by the game API
+#[derive(Serialize)]
+struct GameView {
+    gameid: String,
+    dev: bool,
+    protocol: u32,
+    players: Vec<String>,
+    eliminated: Vec<String>,
+    mode: GameMode,
+    next_player: Option<String>,
+    pending_reports: Vec<String>,
+    // Area of the scan waiting for an answer, e.g. "B3:D5"
+    pending_scan: Option<String>,
+    // Fleets placed hidden mines at join
+    mines: bool,
+    winner: Option<String>,
+    // Every fleet afloat agreed to a draw
+    drawn: bool,
+    // Fleets that agreed to the draw on offer, the one that offered it first
+    draw_offer: Vec<String>,
+    // Draw offers made in the game, the next offer is numbered one more
+    draw_offers: u32,
+    tournament: Option<String>,
+    // Fleets of each team, empty for a game without teams
+    teams: BTreeMap<String, Vec<String>>,
+    // Fleets revealed once the game was over
+    revealed: BTreeMap<String, Revealed>,
+    // Every shot fired so far, in order
+    shots: Vec<ShotView>,
+}
+
+#[derive(Serialize)]
+struct ShotView {
+    shooter: String,
+    target: String,
+    pos: String,
+    // None until the target reports the shot
+    result: Option<String>,
+}
+
+// A row of the leaderboard
+#[derive(Serialize)]
+struct Standing {
+    rank: usize,
+    fleet: String,
+    rating: f64,
+    games: u32,
+    wins: u32,
+    draws: u32,
+}
+
+// Methods this node was built with, plus older versions still accepted during an
+// upgrade, listed as a JSON array of method sets in the file named by ACCEPTED_METHODS
+fn accepted_methods() -> Result<Vec<MethodSet>, String> {
+    let mut methods = vec![MethodSet {
+        protocol: PROTOCOL_VERSION,
+        join: JOIN_ID.into(),
+        fire: FIRE_ID.into(),
+        report: REPORT_ID.into(),
+        batch_report: BATCH_REPORT_ID.into(),
+        wave: WAVE_ID.into(),
+        win: WIN_ID.into(),
+        scan: SCAN_ID.into(),
+        scan_report: SCAN_REPORT_ID.into(),
+        move_ship: MOVE_SHIP_ID.into(),
+        ownership: OWNERSHIP_ID.into(),
+    }];
+    if let Ok(path) = std::env::var("ACCEPTED_METHODS") {
+        let json = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read ACCEPTED_METHODS file {}: {}", path, err))?;
+        let extra: Vec<MethodSet> = serde_json::from_str(&json)
+            .map_err(|err| format!("Invalid ACCEPTED_METHODS file {}: {}", path, err))?;
+        methods.extend(extra.into_iter().filter(|m| m.protocol != PROTOCOL_VERSION));
+    }
+    Ok(methods)
+}
+
+// Handler to serve the HTML page
+async fn index() -> Html<&'static str> {
+    Html(
+        r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Blockchain Emulator</title>
+        </head>
+        <body>
+            <h1>Registered Transactions</h1>          
+            <ul id="logs"></ul>
+            <script>
+                const eventSource = new EventSource('/logs');
+                eventSource.onmessage = function(event) {
+                    const logs = document.getElementById('logs');
+                    const log = document.createElement('li');
+                    log.textContent = event.data;
+                    logs.appendChild(log);
+                };
+            </script>
+        </body>
+        </html>
+        "#,
+    )
+}
+
+// Handler to manage SSE connections
+#[axum::debug_handler]
+async fn logs(Extension(shared): Extension<SharedData>) -> impl IntoResponse {
+    let rx = BroadcastStream::new(shared.tx.subscribe());
+    let stream = rx.filter_map(|result| async move {
+        match result {
+            Ok(msg) => Some(Ok(Event::default().data(msg))),
+            Err(_) => Some(Err(Box::<dyn Error + Send + Sync>::from("Error"))),
+        }
+    });
+
+    axum::response::sse::Sse::new(stream)
+}
+
+// Handler to stream the events of one game. Replays the game from its start, or from
+// after the Last-Event-ID a reconnecting client sends, then follows it live.
+async fn game_events(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !shared.game_logs.lock().unwrap().contains_key(&gameid) {
+        return (StatusCode::NOT_FOUND, format!("Game {} does not exist", gameid)).into_response();
+    }
+    let next = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<usize>().ok())
+        .map_or(0, |id| id + 1);
+
+    // The game's log is the source of events, the channel only wakes the stream up,
+    // so a receiver that lags behind loses nothing
+    let rx = shared.logged.subscribe();
+    let stream = futures::stream::unfold((shared, gameid, next, rx), |(shared, gameid, next, mut rx)| async move {
+        loop {
+            let msg = shared.game_logs.lock().unwrap().get(&gameid).and_then(|log| log.get(next).cloned());
+            if let Some(msg) = msg {
+                let event = Event::default().id(next.to_string()).data(msg);
+                return Some((Ok::<_, Infallible>(event), (shared, gameid, next + 1, rx)));
+            }
+            if let Err(broadcast::error::RecvError::Closed) = rx.recv().await {
+                return None;
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+// Handler to serve a page following one game
+async fn watch(Path(gameid): Path<String>) -> Html<String> {
+    let html = r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Game {gameid}</title>
+        </head>
+        <body>
+            <h1>Game {gameid}</h1>
+            <ul id="logs"></ul>
+            <script>
+                const eventSource = new EventSource(location.pathname.replace(/\/watch$/, '/events'));
+                eventSource.onmessage = function(event) {
+                    const logs = document.getElementById('logs');
+                    const log = document.createElement('li');
+                    log.textContent = event.data;
+                    logs.appendChild(log);
+                };
+            </script>
+        </body>
+        </html>
+        "#;
+    Html(html.replace("{gameid}", &html_escape(&gameid)))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Handler to publish the image IDs the node accepts
+async fn version(Extension(shared): Extension<SharedData>) -> Json<VersionInfo> {
+    Json(VersionInfo {
+        protocol: PROTOCOL_VERSION,
+        accepted: shared.methods.to_vec(),
+    })
+}
+
+// Handler to return the state of one game
+async fn game_state(Extension(shared): Extension<SharedData>, Path(gameid): Path<String>) -> impl IntoResponse {
+    let gmap = shared.gmap.lock().unwrap();
+    match gmap.get(&gameid) {
+        Some(game) => {
+            let mut players: Vec<String> = game.pmap.keys().cloned().collect();
+            players.sort();
+            let eliminated = game.order.iter().filter(|f| game.pmap[*f].eliminated()).cloned().collect();
+            Json(GameView {
+                gameid,
+                dev: game.dev,
+                protocol: game.protocol,
+                players,
+                eliminated,
+                mode: game.mode,
+                next_player: game.next_player.clone(),
+                pending_reports: game.pending_reports.iter().map(|&pos| xy_pos(pos)).collect(),
+                pending_scan: game.pending_scan.map(|area| xy_area(&area)),
+                mines: game.mined(),
+                winner: game.winner.clone(),
+                drawn: game.drawn,
+                draw_offer: game.draw.clone(),
+                draw_offers: game.draw_offers,
+                tournament: game.tournament.clone(),
+                teams: game.team_fleets(),
+                revealed: game.pmap.iter().filter_map(|(fleet, player)| Some((fleet.clone(), player.revealed.clone()?))).collect(),
+                shots: game.shots.iter().map(|shot| ShotView {
+                    shooter: shot.shooter.clone(),
+                    target: shot.target.clone(),
+                    pos: xy_pos(shot.pos),
+                    result: shot.result.clone(),
+                }).collect(),
+            })
+            .into_response()
+        }
+        None => (StatusCode::NOT_FOUND, format!("Game {} does not exist", gameid)).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct TranscriptQuery {
+    // Adds every move's receipt, for an audit
+    #[serde(default)]
+    receipts: bool,
+}
+
+// Handler to export a game's transcript, its accepted moves in order
+async fn game_transcript(
+    Extension(shared): Extension<SharedData>,
+    Path(gameid): Path<String>,
+    Query(query): Query<TranscriptQuery>,
+) -> impl IntoResponse {
+    let ledger = shared.ledger.lock().unwrap_or_else(|e| e.into_inner());
+    let gmap = shared.gmap.lock().unwrap();
+    let Some(game) = gmap.get(&gameid) else {
+        return (StatusCode::NOT_FOUND, format!("Game {} does not exist", gameid)).into_response();
+    };
+    let Some(methods) = shared.methods.iter().find(|methods| methods.protocol == game.protocol) else {
+        return (StatusCode::NOT_FOUND, format!("Protocol {} is no longer accepted", game.protocol)).into_response();
+    };
+    let mut moves = Vec::new();
+    for (index, entry) in ledger.entries().iter().enumerate() {
+        let Transaction::Move(data) = &entry.transaction else {
+            continue;
+        };
+        let Some(journal) = journal_json(data) else {
+            continue;
+        };
+        if journal["gameid"] != gameid.as_str() {
+            continue;
+        }
+        moves.push(TranscriptMove {
+            index,
+            time: entry.time,
+            cmd: data.cmd,
+            mode: data.mode,
+            fleet: journal["fleet"].as_str().unwrap_or_default().to_string(),
+            image_id: methods.image_id(data.cmd),
+            journal,
+            events: entry.events.clone(),
+            receipt: query.receipts.then(|| data.receipt.clone()),
+        });
+    }
+    Json(Transcript {
+        gameid: gameid.clone(),
+        mode: game.mode,
+        protocol: game.protocol,
+        dev: game.dev,
+        tournament: game.tournament.clone(),
+        seats: game.seats.clone(),
+        teams: game.team_fleets(),
+        winner: game.winner.clone(),
+        drawn: game.drawn,
+        moves,
+    })
+    .into_response()
+}
+
+// Handler to serve a page stepping through a game's transcript move by move
+async fn replay(Path(gameid): Path<String>) -> Html<String> {
+    let html = r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <title>Replay of game {gameid}</title>
+            <style>
+                .boards { display: flex; flex-wrap: wrap; gap: 20px; }
+                .board { display: grid; grid-template-columns: repeat(10, 20px); gap: 1px; }
+                .cell { width: 20px; height: 20px; border: 1px solid #ccc; }
+                .Shot { background-color: yellow; }
+                .Miss { background-color: lightblue; }
+                .Hit { background-color: red; }
+                .Mine { background-color: orange; }
+                .Ship { outline: 2px solid black; outline-offset: -3px; }
+                .misreport { color: red; }
+                .current { font-weight: bold; }
+            </style>
+        </head>
+        <body>
+            <h1>Replay of game {gameid}</h1>
+            <p id="summary"></p>
+            <button id="first">|&lt;</button>
+            <button id="prev">&lt;</button>
+            <span id="step"></span>
+            <button id="next">&gt;</button>
+            <button id="last">&gt;|</button>
+            <h2 id="move"></h2>
+            <div class="boards" id="boards"></div>
+            <ul id="events"></ul>
+            <pre id="journal"></pre>
+            <script>
+                let transcript = null;
+                let step = 0;
+                // Boards the fleets revealed once the game was over
+                let revealed = {};
+
+                // Board position as the chain writes it, e.g. 23 is D2
+                function xy(pos) {
+                    return String.fromCharCode(65 + pos % 10) + Math.floor(pos / 10);
+                }
+
+                // Shots on every fleet's board after the first moves of the transcript
+                function boards(count) {
+                    const shots = {};
+                    for (const move of transcript.moves.slice(0, count)) {
+                        const journal = move.journal;
+                        if (move.cmd === 'Join') {
+                            shots[move.fleet] = {};
+                        } else if (move.cmd === 'Fire') {
+                            for (const pos of journal.positions) {
+                                shots[journal.target][pos] = 'Shot';
+                            }
+                        } else if (move.cmd === 'Report') {
+                            shots[move.fleet][journal.pos] = journal.report;
+                        } else if (move.cmd === 'BatchReport') {
+                            journal.positions.forEach((pos, i) => shots[move.fleet][pos] = journal.results[i]);
+                        }
+                    }
+                    return shots;
+                }
+
+                function show() {
+                    const count = transcript.moves.length;
+                    document.getElementById('step').textContent = `Move ${step} of ${count}`;
+                    const move = transcript.moves[step - 1];
+                    document.getElementById('move').textContent = move
+                        ? `${move.cmd} by ${move.fleet} at ${new Date(move.time).toLocaleString()}`
+                        : 'Before the first move';
+                    document.getElementById('journal').textContent = move ? JSON.stringify(move.journal, null, 2) : '';
+
+                    const container = document.getElementById('boards');
+                    container.innerHTML = '';
+                    for (const [fleet, shots] of Object.entries(boards(step))) {
+                        const board = document.createElement('div');
+                        const title = document.createElement('div');
+                        const reveal = revealed[fleet];
+                        title.textContent = reveal ? `${fleet}, revealed` : fleet;
+                        const grid = document.createElement('div');
+                        grid.className = 'board';
+                        for (let pos = 0; pos < 100; pos++) {
+                            const cell = document.createElement('div');
+                            cell.className = 'cell ' + (shots[pos] || '');
+                            if (reveal && reveal.board.includes(xy(pos))) {
+                                cell.classList.add('Ship');
+                            }
+                            grid.appendChild(cell);
+                        }
+                        board.append(title, grid);
+                        for (const misreport of reveal ? reveal.misreports : []) {
+                            const flag = document.createElement('div');
+                            flag.className = 'misreport';
+                            flag.textContent = `Misreported: ${misreport}`;
+                            board.appendChild(flag);
+                        }
+                        container.appendChild(board);
+                    }
+
+                    const events = document.getElementById('events');
+                    events.innerHTML = '';
+                    transcript.moves.slice(0, step).forEach((played, i) => {
+                        for (const text of played.events) {
+                            const event = document.createElement('li');
+                            event.textContent = text;
+                            event.className = i === step - 1 ? 'current' : '';
+                            events.appendChild(event);
+                        }
+                    });
+                }
+
+                function go(to) {
+                    step = Math.max(0, Math.min(transcript.moves.length, to));
+                    show();
+                }
+
+                const load = path => fetch(location.pathname.replace(/\/replay$/, path))
+                    .then(response => response.ok ? response.json() : Promise.reject(response.statusText));
+                Promise.all([load('/transcript'), load('')])
+                    .then(([loaded, game]) => {
+                        transcript = loaded;
+                        revealed = game.revealed;
+                        const winner = transcript.winner ? `, won by ${transcript.winner}` : transcript.drawn ? ', drawn' : '';
+                        document.getElementById('summary').textContent =
+                            `${transcript.mode} game, protocol ${transcript.protocol}${transcript.dev ? ', dev' : ''}${winner}`;
+                        document.getElementById('first').onclick = () => go(0);
+                        document.getElementById('prev').onclick = () => go(step - 1);
+                        document.getElementById('next').onclick = () => go(step + 1);
+                        document.getElementById('last').onclick = () => go(transcript.moves.length);
+                        show();
+                    })
+                    .catch(error => document.getElementById('summary').textContent = `Could not load the game: ${error}`);
+            </script>
+        </body>
+        </html>
+        "#;
+    Html(html.replace("{gameid}", &html_escape(&gameid)))
+}
+
+// Ratings replayed from the results of the games that ended, of the dev games or the
+// real ones
+fn current_ratings(shared: &SharedData, dev: bool) -> HashMap<String, PlayerRating> {
+    let results = if dev { &shared.dev_results } else { &shared.results };
+    ratings(&results.lock().unwrap())
+}
+
+// Keeps the result of a game that just ended for the ratings
+fn record_result(shared: &SharedData, game: &Game, gameid: &str) {
+    let results = if game.dev { &shared.dev_results } else { &shared.results };
+    results.lock().unwrap().push(game.result(gameid));
+}
+
+#[derive(Deserialize)]
+struct RatingsQuery {
+    // Ratings from the dev games instead of the real ones
+    #[serde(default)]
+    dev: bool,
+}
+
+// Handler for the leaderboard, best rating first
+async fn leaderboard(Extension(shared): Extension<SharedData>, Query(query): Query<RatingsQuery>) -> Json<Vec<Standing>> {
+    let mut players: Vec<PlayerRating> = current_ratings(&shared, query.dev).into_values().collect();
+    players.sort_by(|a, b| b.rating.total_cmp(&a.rating).then_with(|| a.fleet.cmp(&b.fleet)));
+    Json(
+        players
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| Standing {
+                rank: i + 1,
+                fleet: p.fleet,
+                rating: p.rating,
+                games: p.games,
+                wins: p.wins,
+                draws: p.draws,
+            })
+            .collect(),
+    )
+}
+
+// Handler for a fleet's rating and how every rated game changed it
+async fn player_rating(
+    Extension(shared): Extension<SharedData>,
+    Path(fleet): Path<String>,
+    Query(query): Query<RatingsQuery>,
+) -> impl IntoResponse {
+    match current_ratings(&shared, query.dev).remove(&fleet) {
+        Some(player) => Json(player).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Player {} has no rated games", fleet)).into_response(),
+    }
+}
+
+// Handler for tournament transactions
+async fn tournament_action(
+    Extension(shared): Extension<SharedData>,
+    Json(command): Json<TournamentCommand>,
+) -> String {
+    submit(&shared, Transaction::Tournament(command))
+}
+
+// Handler for a tournament with its fleets and every match drawn so far
+async fn tournament_state(
+    Extension(shared): Extension<SharedData>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match shared.tournaments.lock().unwrap().get(&id) {
+        Some(tournament) => Json(tournament.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Tournament {} does not exist", id)).into_response(),
+    }
+}
+
+// Handler for a tournament's standings, leader first
+async fn tournament_standings(
+    Extension(shared): Extension<SharedData>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match shared.tournaments.lock().unwrap().get(&id) {
+        Some(tournament) => Json(tournament.standings()).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Tournament {} does not exist", id)).into_response(),
+    }
+}
+
+fn xy_pos(pos: u8) -> String {
+    let x = pos % 10;
+    let y = pos / 10;
+    format!("{}{}", (x + 65) as char, y)
+}
+
+// Positions of a salvo, e.g. "B3,C4"
+fn xy_list(positions: &[u8]) -> String {
+    positions.iter().map(|&pos| xy_pos(pos)).collect::<Vec<String>>().join(",")
+}
+
+// Corners of a scanned area, e.g. "B3:D5"
+fn xy_area(area: &ScanArea) -> String {
+    format!("{}:{}", xy_pos(area.pos), xy_pos(area.last()))
+}
+
+fn receipt_kind(receipt: &Receipt) -> &'static str {
+    match receipt.inner {
+        InnerReceipt::Composite(_) => "composite",
+        InnerReceipt::Succinct(_) => "succinct",
+        InnerReceipt::Groth16(_) => "groth16",
+        InnerReceipt::Fake(_) => "fake",
+        _ => "unknown",
+    }
+}
+
+// Image ID of the method the receipt claims to prove, the pre-state digest of its claim
+fn claimed_image_id(receipt: &Receipt) -> Option<Digest> {
+    let claim = receipt.claim().ok()?;
+    Some(claim.as_value().ok()?.pre.digest::<sha::Impl>())
+}
+
+// Whether the game takes fake receipts and which protocol version its receipts must use.
+// A game that does not exist yet takes any accepted version, and becomes a dev game
+// if it is started with a fake receipt. A tournament match is a dev game only in a dev
+// tournament, so a fake receipt can't decide a real match.
+fn game_policy(shared: &SharedData, gameid: &str) -> (bool, Option<u32>) {
+    match shared.gmap.lock().unwrap().get(gameid) {
+        Some(game) if !game.order.is_empty() => (game.dev, Some(game.protocol)),
+        Some(game) if game.tournament.is_some() => (game.dev, None),
+        _ => (true, None),
+    }
+}
+
+// Verifies a receipt of any kind and logs its kind, size and how long verification took.
+// Fake receipts are only let through for dev games when the policy allows them.
+// Returns the kind of the accepted receipt and the methods it was verified against.
+fn verify_receipt(shared: &SharedData, receipt: &Receipt, cmd: Command, action: &str, policy: (bool, Option<u32>)) -> Option<(&'static str, MethodSet)> {
+    let (dev_game, protocol) = policy;
+    let mut candidates = shared
+        .methods
+        .iter()
+        .filter(|methods| protocol.is_none() || protocol == Some(methods.protocol));
+
+    let kind = receipt_kind(receipt);
+    if kind == "fake" {
+        if shared.dev_policy == DevPolicy::DevGames && dev_game {
+            // Proves nothing, but must still claim to come from the command's method
+            let claimed = claimed_image_id(receipt);
+            if let Some(methods) = candidates.find(|methods| Some(methods.image_id(cmd)) == claimed) {
+                return Some((kind, methods.clone()));
+            }
+            shared.tx.send(format!("Attempting to {} with a fake receipt of another method", action)).unwrap();
+            return None;
+        }
+        shared.tx.send(format!("Attempting to {} with a fake receipt", action)).unwrap();
+        return None;
+    }
+
+    let size = serde_json::to_vec(receipt).map_or(0, |bytes| bytes.len());
+    let start = Instant::now();
+    let verified = candidates.find(|methods| receipt.verify(methods.image_id(cmd)).is_ok());
+    let elapsed = start.elapsed();
+
+    let Some(methods) = verified else {
+        shared.tx.send(format!("Attempting to {} with invalid receipt", action)).unwrap();
+        return None;
+    };
+    let msg = format!(
+        "Verified {} receipt of {} bytes for protocol {} in {} ms",
+        kind,
+        size,
+        methods.protocol,
+        elapsed.as_millis()
+    );
+    println!("{}", msg);
+    shared.tx.send(msg).unwrap();
+    Some((kind, methods.clone()))
+}
+
+// Verifies the move's receipt, then decodes its journal. A journal that does not decode
+// is refused like an invalid receipt, so a malformed post can't panic the node.
+fn verified_journal<T: DeserializeOwned>(shared: &SharedData, input_data: &CommunicationData, cmd: Command, action: &str) -> Option<(T, &'static str, MethodSet)> {
+    let Some((gameid, _)) = move_of(input_data) else {
+        shared.tx.send(format!("Attempting to {} with a journal that does not decode", action)).unwrap();
+        return None;
+    };
+    let policy = game_policy(shared, &gameid);
+    let (kind, methods) = verify_receipt(shared, &input_data.receipt, cmd, action, policy)?;
+    match input_data.receipt.journal.decode() {
+        Ok(data) => Some((data, kind, methods)),
+        Err(_) => None,
+    }
+}
+
+async fn smart_contract(
+    Extension(shared): Extension<SharedData>,
+    Json(input_data): Json<CommunicationData>,
+) -> String {
+    submit(&shared, Transaction::Move(input_data))
+}
+
+async fn reveal_fleet(
+    Extension(shared): Extension<SharedData>,
+    Json(reveal): Json<RevealData>,
+) -> String {
+    submit(&shared, Transaction::Reveal(reveal))
+}
+
+// Applies the transaction and keeps it in the log if accepted. One transaction at a
+// time, so the log keeps them in the order they were applied. A panic while applying
+// one leaves the log as it was, so the lock is taken back rather than given up on.
+fn submit(shared: &SharedData, transaction: Transaction) -> String {
+    let mut ledger = shared.ledger.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entry = Entry::now(transaction);
+    let response = apply_entry(shared, &mut entry);
+    if response == "OK" {
+        ledger.append(entry);
+    }
+    response
+}
+
+// Applies the logged transaction and keeps the messages it published about its game.
+// Only an accepted transaction adds its messages to the games' logs: a refused one is
+// not in the ledger, so after a restart the logs and their event ids would differ.
+fn apply_entry(shared: &SharedData, entry: &mut Entry) -> String {
+    let gameid = match &entry.transaction {
+        Transaction::Move(data) => move_of(data).map(|(gameid, _)| gameid),
+        Transaction::Tournament(_) => None,
+        Transaction::Reveal(reveal) => Some(reveal.gameid.clone()),
+    };
+    shared.pending.lock().unwrap_or_else(|e| e.into_inner()).clear(); //left over by a panic
+    let response = apply_transaction(shared, &entry.transaction);
+    let published = std::mem::take(&mut *shared.pending.lock().unwrap());
+    entry.events = Vec::new();
+    if response == "OK" {
+        let mut logs = shared.game_logs.lock().unwrap();
+        for (game, msg) in published {
+            if let Some(log) = logs.get_mut(&game) {
+                if gameid.as_ref() == Some(&game) {
+                    entry.events.push(msg.clone());
+                }
+                log.push(msg);
+            }
+        }
+        let _ = shared.logged.send(());
+    }
+    response
+}
+
+fn apply_transaction(shared: &SharedData, transaction: &Transaction) -> String {
+    match transaction {
+        Transaction::Move(input_data) => apply(shared, input_data),
+        Transaction::Tournament(command) => apply_tournament(shared, command),
+        Transaction::Reveal(reveal) => handle_reveal(shared, reveal),
+    }
+}
+
+fn apply(shared: &SharedData, input_data: &CommunicationData) -> String {
+    match input_data.cmd {
+        Command::Join => handle_join(shared, input_data),
+        Command::Fire => handle_fire(shared, input_data),
+        Command::Report => handle_report(shared, input_data),
+        Command::BatchReport => handle_batch_report(shared, input_data),
+        Command::Wave => handle_wave(shared, input_data),
+        Command::Win => handle_win(shared, input_data),
+        Command::Scan => handle_scan(shared, input_data),
+        Command::ScanReport => handle_scan_report(shared, input_data),
+        Command::Move => handle_move(shared, input_data),
+        Command::Resign => handle_resign(shared, input_data),
+        Command::OfferDraw => handle_offer_draw(shared, input_data),
+        Command::AcceptDraw => handle_accept_draw(shared, input_data),
+    }
+}
+
+// Runs a tournament transaction, the node creates the games of the matches it draws
+fn apply_tournament(shared: &SharedData, command: &TournamentCommand) -> String {
+    let reject = |msg: String| {
+        let _ = shared.tx.send(msg.clone());
+        msg
+    };
+    let mut gmap = shared.gmap.lock().unwrap();
+    let mut tournaments = shared.tournaments.lock().unwrap();
+    match command {
+        TournamentCommand::Create { tournament, format, mode, dev } => {
+            if tournaments.contains_key(tournament) {
+                return reject(format!("Tournament {} already exists", tournament));
+            }
+            if *dev && shared.dev_policy != DevPolicy::DevGames {
+                return reject(format!("Tournament {} can't be played with fake receipts on this node", tournament));
+            }
+            let created = Tournament::new(tournament, *format, *mode, *dev);
+            if let Some(gameid) = gmap.keys().find(|id| id.starts_with(&created.game_prefix())) {
+                return reject(format!("Game {} already uses the game ids of tournament {}", gameid, tournament));
+            }
+            tournaments.insert(tournament.clone(), created);
+            let _ = shared.tx.send(format!("Tournament {} created, {:?} in {:?} mode", tournament, format, mode));
+        }
+        TournamentCommand::Register { tournament, fleet } => {
+            let Some(registering) = tournaments.get_mut(tournament) else {
+                return reject(format!("Tournament {} does not exist", tournament));
+            };
+            if registering.started {
+                return reject(format!("Tournament {} already started", tournament));
+            }
+            if registering.fleets.contains(fleet) {
+                return reject(format!("Player {} already registered in tournament {}", fleet, tournament));
+            }
+            registering.fleets.push(fleet.clone());
+            let _ = shared.tx.send(format!("Player {} registered in tournament {}", fleet, tournament));
+        }
+        TournamentCommand::Start { tournament } => {
+            let Some(starting) = tournaments.get_mut(tournament) else {
+                return reject(format!("Tournament {} does not exist", tournament));
+            };
+            if starting.started {
+                return reject(format!("Tournament {} already started", tournament));
+            }
+            if starting.fleets.len() < 2 {
+                return reject(format!("Tournament {} needs at least 2 fleets", tournament));
+            }
+            let drawn = starting.start();
+            let _ = shared.tx.send(format!("Tournament {} started with {} fleets", tournament, starting.fleets.len()));
+            open_matches(shared, &mut gmap, starting, drawn);
+        }
+    }
+    "OK".to_string()
+}
+
+// Creates the games of newly drawn matches and announces them
+fn open_matches(shared: &SharedData, gmap: &mut HashMap<String, Game>, tournament: &Tournament, drawn: Vec<Match>) {
+    for next in drawn {
+        let Some(gameid) = next.gameid else {
+            let _ = shared.tx.send(format!(
+                "Tournament {} round {}: player {} goes through on a bye",
+                tournament.id, next.round, next.fleets[0]
+            ));
+            continue;
+        };
+        let mut game = Game::new(tournament.mode);
+        game.seats = next.fleets.clone();
+        game.tournament = Some(tournament.id.clone());
+        game.dev = tournament.dev;
+        gmap.insert(gameid.clone(), game);
+        shared.game_logs.lock().unwrap().insert(gameid.clone(), Vec::new());
+        let msg = format!(
+            "Tournament {} round {}: {} play game {}",
+            tournament.id, next.round, next.fleets.join(" vs "), gameid
+        );
+        shared.publish(&gameid, msg);
+    }
+}
+
+// Collects the result of a tournament match, opening the next round once one is complete
+fn record_match(shared: &SharedData, gmap: &mut HashMap<String, Game>, tournament: &str, gameid: &str, winner: &str) {
+    let mut tournaments = shared.tournaments.lock().unwrap();
+    let Some(playing) = tournaments.get_mut(tournament) else {
+        return;
+    };
+    let drawn = playing.record(gameid, winner);
+    open_matches(shared, gmap, playing, drawn);
+    if playing.finished {
+        let msg = match &playing.champion {
+            Some(champion) => format!("Tournament {} is over, player {} won", tournament, champion),
+            None => format!("Tournament {} is over, tied at the top", tournament),
+        };
+        let _ = shared.tx.send(msg);
+    }
+}
+
+// Tournament whose games the id is kept for, if any
+fn reserved_by(shared: &SharedData, gameid: &str) -> Option<String> {
+    shared
+        .tournaments
+        .lock()
+        .unwrap()
+        .values()
+        .find(|t| gameid.starts_with(&t.game_prefix()))
+        .map(|t| t.id.clone())
+}
+
+fn handle_join(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<BaseJournal>(shared, input_data, Command::Join, "join game") else {
+        return "Could not verify receipt".to_string();
+    };
+    let mut gmap = shared.gmap.lock().unwrap();
+    if !gmap.contains_key(&data.gameid) {
+        if let Some(tournament) = reserved_by(shared, &data.gameid) { //only the node creates tournament games
+            let mesg = format!("Game {} is reserved for tournament {}", data.gameid, tournament);
+            shared.publish(&data.gameid, mesg.clone());
+            return mesg;
+        }
+    }
+    let game = gmap
+        .entry(data.gameid.clone())
+        .or_insert_with(|| Game::new(input_data.mode.unwrap_or_default()));
+    shared.game_logs.lock().unwrap().entry(data.gameid.clone()).or_default();
+    if game.started {
+        let mesg = format!("Game {} already started", data.gameid);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if input_data.mode.is_some_and(|mode| mode != game.mode) {
+        let mesg = format!("Game {} is played in {:?} mode", data.gameid, game.mode);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if !game.seats.is_empty() && !game.seats.contains(&data.fleet) {
+        let mesg = format!("Player {} is not drawn to play game {}", data.fleet, data.gameid);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if data.team.as_ref().is_some_and(|team| team.is_empty() || team.contains(char::is_whitespace)) {
+        let mesg = format!("Player {} declared an invalid team name", data.fleet);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if data.team.is_some() && game.tournament.is_some() { //tournament matches are one on one
+        let mesg = format!("Game {} is a tournament match, it is not played in teams", data.gameid);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if !game.order.is_empty() && data.team.is_some() != game.teamed() { //all fleets declare a team or none does
+        let mesg = if game.teamed() {
+            format!("Game {} is played in teams, declare one to join", data.gameid)
+        } else {
+            format!("Game {} is not played in teams", data.gameid)
+        };
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if data.mines.is_some() && game.mode == GameMode::Salvo { //salvos are reported without mines
+        let mesg = format!("Game {} is played in Salvo mode, it is not played with mines", data.gameid);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if !game.order.is_empty() && data.mines.is_some() != game.mined() { //all fleets place mines or none does
+        let mesg = if game.mined() {
+            format!("Game {} is played with mines, place {} to join", data.gameid, FLEET_MINES)
+        } else {
+            format!("Game {} is not played with mines", data.gameid)
+        };
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if game.pmap.contains_key(&data.fleet) {
+        let mesg = format!("Player {} already in game {}", data.fleet, data.gameid);
+        shared.publish(&data.gameid, mesg.clone());
+        return mesg;
+    }
+    if game.order.is_empty() { //the first fleet to join sets the game up and fires first
+        game.dev = kind == "fake";
+        game.protocol = methods.protocol;
+        game.next_player = Some(data.fleet.clone());
+    }
+    game.pmap.insert(data.fleet.clone(), Player {
+        name: data.fleet.clone(),
+        current_state: data.board.clone(),
+        origin: data.board,
+        reports: 0,
+        history: None,
+        hits: Vec::new(),
+        team: data.team.clone(),
+        scans: 0,
+        mines: data.mines,
+        loses_turn: false,
+        resigned: false,
+        reported: Vec::new(),
+        moved_after: None,
+        revealed: None,
+    });
+    game.order.push(data.fleet.clone());
+    let mut mesg = format!("[{}] Player {} joined game {}", kind, data.fleet, data.gameid);
+    if let Some(team) = &data.team {
+        mesg.push_str(&format!(" for team {}", team));
+    }
+    shared.publish(&data.gameid, mesg);
+    "OK".to_string()
+}
+
+fn handle_fire(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<FireJournal>(shared, input_data, Command::Fire, "fire") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+
+        if game.pmap.contains_key(&data.fleet) { //check if the fleet exists
+            if game.next_player == Some(data.fleet.clone()) { //check if it is this players turn
+                if !game.awaiting_answer() { //check if the previous report has been addressed
+                    if game.mode != GameMode::Salvo && data.positions.len() != 1 { //one shot per turn
+                        let msg = format!("Player {} can fire only one shot per turn in game {}", data.fleet, data.gameid);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.mode == GameMode::Salvo && !fires_from_board(&game.pmap[&data.fleet], &data) { //the shots were counted on the fleet's board
+                        let msg = format!("Player {} fired from the wrong board", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if let Some(pos) = data.positions.iter().find(|&&pos| !on_board(pos)) { //shots land on the board
+                        let msg = format!("Player {} fired off the board at position {}", data.fleet, pos);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if let Some(&pos) = data.positions.iter().enumerate().find_map(|(i, pos)| {
+                        (data.positions[..i].contains(pos) || game.fired_at(&data.fleet, &data.target, *pos)).then_some(pos)
+                    }) { //every cell is fired at once
+                        let msg = format!("Player {} already fired at player {} at pos {}", data.fleet, data.target, xy_pos(pos));
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if data.target == data.fleet { //check the fleet is not firing at itself
+                        let msg = format!("Player {} can't fire at their own fleet", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.get(&data.target).is_some_and(|target| target.eliminated()) { //check the target is still afloat
+                        let msg = format!("Player {} is already eliminated", data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.contains_key(&data.target) && game.allies(&data.fleet, &data.target) { //teammates don't fire at each other
+                        let msg = format!("Player {} can't fire at teammate {}", data.fleet, data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.contains_key(&data.target) { //check if the fleet exists
+                        game.next_player = Some(data.target.clone());
+                        game.pending_reports = data.positions.clone();
+                        game.shooter = Some(data.fleet.clone());
+                        game.started = true;
+                        game.shots.extend(data.positions.iter().map(|&pos| Shot {
+                            shooter: data.fleet.clone(),
+                            target: data.target.clone(),
+                            pos,
+                            result: None,
+                        }));
+                        let msg = format!("[{}] Player {} fired at player {} at pos {}", kind, data.fleet, data.target, xy_list(&data.positions));
+                        shared.publish(&data.gameid, msg);
+                        withdraw_draw(shared, game, &data.gameid);
+                    }
+
+                    else {
+                        let msg = format!("Player {} not in game", data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                }
+                else {
+                    let msg = format!("Must address report first");
+                    shared.publish(&data.gameid, msg.clone());
+                    return msg;
+                }
+            }
+            else {
+                let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+// A salvo is only as large as the ships afloat on the board the fleet committed to
+fn fires_from_board(player: &Player, data: &FireJournal) -> bool {
+    data.board == player.current_state && data.origin == player.origin
+}
+
+// The report receipt proved it extends a previous receipt; check that one was
+// verified against the game's methods and is the latest we accepted for this fleet
+fn continues_history(player: &Player, data: &ReportJournal, methods: &MethodSet) -> bool {
+    data.join_id == methods.join
+        && data.report_id == methods.report
+        && data.move_id == methods.move_ship
+        && data.origin == player.origin
+        && data.reports == player.reports + 1
+}
+
+fn handle_report(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<ReportJournal>(shared, input_data, Command::Report, "report") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if let Some(player) = game.pmap.get_mut(&data.fleet) { //check if the fleet exists
+            if game.pending_reports.is_empty() { // Check if there is a report to handle
+                let msg = format!("No report to handle in this game");
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if game.mode == GameMode::Salvo { // Salvos are reported in one batch
+                let msg = format!("Game {} is played in {:?} mode, report every shot at once", data.gameid, game.mode);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else {
+                if game.next_player == Some(data.fleet.clone()) { //check if turn
+                    if player.current_state == data.board { //Check if report is for the correct board
+                        if !continues_history(player, &data, &methods) { //check the report extends the fleet's proven history
+                            let msg = format!("Player {} reported with a broken board history", data.fleet);
+                            shared.publish(&data.gameid, msg.clone());
+                            return msg;
+                        }
+                        else if data.mines != player.mines { //check the shot was checked against the mines placed at join
+                            let msg = format!("Player {} reported without the mines placed at join", data.fleet);
+                            shared.publish(&data.gameid, msg.clone());
+                            return msg;
+                        }
+                        else if game.pending_reports == [data.pos] { //check if report is for the correct position
+
+                            player.current_state = data.next_board.clone();
+                            player.reports = data.reports;
+                            player.history = Some(input_data.receipt.clone());
+                            player.reported.push((data.pos, data.report.clone()));
+
+                            let msg = format!("[{}] Player {} reported {} at pos {}", kind, data.fleet, data.report, xy_pos(data.pos));
+                            shared.publish(&data.gameid, msg);
+                            if data.report == "Mine" { //the shooter sits out its next turn
+                                if let Some(shooter) = game.shooter.clone() {
+                                    game.pmap.get_mut(&shooter).unwrap().loses_turn = true;
+                                    let msg = format!("Player {} fired on a mine and loses their next turn", shooter);
+                                    shared.publish(&data.gameid, msg);
+                                }
+                            }
+                            game.record_results(std::slice::from_ref(&data.report));
+                            let hits = if data.report == "Hit" { vec![data.pos] } else { Vec::new() };
+                            settle_report(shared, game, &data.gameid, &data.fleet, hits);
+                        }
+                        else {
+                            let msg = format!("Player {} reported wrong position. Shot was at pos {}", data.fleet, xy_list(&game.pending_reports));
+                            shared.publish(&data.gameid, msg.clone());
+                            return msg;
+                        }
+                    }
+                    else {
+                        let msg = format!("Player {} reported the wrong board", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                }
+                else {
+                    let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                    shared.publish(&data.gameid, msg.clone());
+                    return msg;
+                }
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+    "OK".to_string()
+}
+
+fn continues_batch_history(player: &Player, data: &BatchReportJournal, methods: &MethodSet) -> bool {
+    data.join_id == methods.join
+        && data.batch_report_id == methods.batch_report
+        && data.origin == player.origin
+        && data.reports == player.reports + 1
+}
+
+fn handle_batch_report(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<BatchReportJournal>(shared, input_data, Command::BatchReport, "report") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if let Some(player) = game.pmap.get_mut(&data.fleet) { //check if the fleet exists
+            if game.pending_reports.is_empty() { // Check if there is a report to handle
+                let msg = format!("No report to handle in this game");
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if game.mode != GameMode::Salvo { // Single shots are reported on their own
+                let msg = format!("Game {} is played in {:?} mode, report the shot on its own", data.gameid, game.mode);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if game.next_player != Some(data.fleet.clone()) { //check if turn
+                let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if player.current_state != data.board { //Check if report is for the correct board
+                let msg = format!("Player {} reported the wrong board", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if !continues_batch_history(player, &data, &methods) { //check the report extends the fleet's proven history
+                let msg = format!("Player {} reported with a broken board history", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if data.positions != game.pending_reports { //check every shot is reported, in the order fired
+                let msg = format!("Player {} reported wrong positions. Shots were at pos {}", data.fleet, xy_list(&game.pending_reports));
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else {
+                player.current_state = data.next_board;
+                player.reports = data.reports;
+                player.history = Some(input_data.receipt.clone());
+                player.reported.extend(data.positions.iter().copied().zip(data.results.iter().cloned()));
+
+                let msg = format!("[{}] Player {} reported {} at pos {}", kind, data.fleet, data.results.join(","), xy_list(&data.positions));
+                shared.publish(&data.gameid, msg);
+                let hits = data
+                    .positions
+                    .iter()
+                    .zip(&data.results)
+                    .filter(|(_, result)| *result == "Hit")
+                    .map(|(&pos, _)| pos)
+                    .collect();
+                game.record_results(&data.results);
+                settle_report(shared, game, &data.gameid, &data.fleet, hits);
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+    "OK".to_string()
+}
+
+// Counts the hits of an accepted report, eliminates a sunk fleet and passes the turn
+// on from the fleet that fired
+fn settle_report(shared: &SharedData, game: &mut Game, gameid: &str, fleet: &str, hits: Vec<u8>) {
+    game.pending_reports.clear();
+    let player = game.pmap.get_mut(fleet).unwrap();
+    let hit = !hits.is_empty();
+    player.hits.extend(hits);
+    if hit && player.eliminated() {
+        let msg = format!("Player {} eliminated from game {}", fleet, gameid);
+        shared.publish(gameid, msg);
+    }
+
+    let shooter = game.shooter.take().unwrap_or(fleet.to_string());
+    game.next_player = game.next_after(&shooter);
+    pass_turn(shared, game, gameid);
+}
+
+// Announces whose turn it is, or that a single fleet is left to claim victory
+fn pass_turn(shared: &SharedData, game: &Game, gameid: &str) {
+    if let Some(team) = game.last_team_standing() {
+        shared.publish(gameid, format!("Team {} is the last team standing in game {}", team, gameid));
+        return;
+    }
+    let alive = game.alive();
+    let msg = match (alive.as_slice(), &game.next_player) {
+        ([last], _) => format!("Player {} is the last fleet standing in game {}", last, gameid),
+        (_, Some(next)) => format!("Player {} to fire in game {}", next, gameid),
+        _ => return,
+    };
+    shared.publish(gameid, msg);
+}
+
+fn handle_scan(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<ScanJournal>(shared, input_data, Command::Scan, "scan") else {
+        return "Could not verify receipt".to_string();
+    };
+    let rules = shared.scan_rules;
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+
+        if game.pmap.contains_key(&data.fleet) { //check if the fleet exists
+            if game.next_player == Some(data.fleet.clone()) { //check if it is this players turn
+                if !game.awaiting_answer() { //check if the previous report has been addressed
+                    if data.board != game.pmap[&data.fleet].current_state { //only a fleet in the game with its own board scans
+                        let msg = format!("Player {} scanned from the wrong board", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if data.area.size != rules.size || !data.area.fits() { //the area lies on the board at the node's size
+                        let msg = format!("Scans in game {} cover {}x{} cells on the board", data.gameid, rules.size, rules.size);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap[&data.fleet].scans >= rules.per_game { //check the fleet has scans left
+                        let msg = format!("Player {} has no scans left in game {}", data.fleet, data.gameid);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if data.target == data.fleet { //check the fleet is not scanning itself
+                        let msg = format!("Player {} can't scan their own fleet", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.get(&data.target).is_some_and(|target| target.eliminated()) { //check the target is still afloat
+                        let msg = format!("Player {} is already eliminated", data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.contains_key(&data.target) && game.allies(&data.fleet, &data.target) { //teammates don't scan each other
+                        let msg = format!("Player {} can't scan teammate {}", data.fleet, data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                    else if game.pmap.contains_key(&data.target) { //check if the fleet exists
+                        game.pmap.get_mut(&data.fleet).unwrap().scans += 1;
+                        game.next_player = Some(data.target.clone());
+                        game.pending_scan = Some(data.area);
+                        game.shooter = Some(data.fleet.clone());
+                        game.started = true;
+                        let msg = format!("[{}] Player {} scanned player {} at {}", kind, data.fleet, data.target, xy_area(&data.area));
+                        shared.publish(&data.gameid, msg);
+                    }
+                    else {
+                        let msg = format!("Player {} not in game", data.target);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                }
+                else {
+                    let msg = format!("Must address report first");
+                    shared.publish(&data.gameid, msg.clone());
+                    return msg;
+                }
+            }
+            else {
+                let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+fn handle_scan_report(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<ScanReportJournal>(shared, input_data, Command::ScanReport, "answer scan") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if let Some(player) = game.pmap.get(&data.fleet) { //check if the fleet exists
+            if let Some(area) = game.pending_scan { // Check if there is a scan to answer
+                if game.next_player == Some(data.fleet.clone()) { //check if turn
+                    if player.current_state == data.board { //the count is taken on the fleet's current board
+                        if data.area == area { //check the answer is for the scanned area
+                            let msg = format!("[{}] Player {} has {} ship cells at {}", kind, data.fleet, data.count, xy_area(&area));
+                            shared.publish(&data.gameid, msg);
+                            game.pending_scan = None;
+                            let shooter = game.shooter.take().unwrap_or(data.fleet.clone());
+                            game.next_player = game.next_after(&shooter);
+                            pass_turn(shared, game, &data.gameid);
+                        }
+                        else {
+                            let msg = format!("Player {} answered for the wrong area. Scan was at {}", data.fleet, xy_area(&area));
+                            shared.publish(&data.gameid, msg.clone());
+                            return msg;
+                        }
+                    }
+                    else {
+                        let msg = format!("Player {} answered with the wrong board", data.fleet);
+                        shared.publish(&data.gameid, msg.clone());
+                        return msg;
+                    }
+                }
+                else {
+                    let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                    shared.publish(&data.gameid, msg.clone());
+                    return msg;
+                }
+            }
+            else {
+                let msg = format!("No scan to answer in this game");
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+    "OK".to_string()
+}
+
+// Same as continues_history, for the receipt of a move
+fn continues_move_history(player: &Player, data: &MoveJournal, methods: &MethodSet) -> bool {
+    data.join_id == methods.join
+        && data.report_id == methods.report
+        && data.move_id == methods.move_ship
+        && data.origin == player.origin
+        && data.reports == player.reports + 1
+}
+
+// The move was checked against the hit cells the fleet reported, in any order
+fn moved_with_hits(player: &Player, data: &MoveJournal) -> bool {
+    let (mut hits, mut reported) = (data.hits.clone(), player.hits.clone());
+    hits.sort();
+    reported.sort();
+    hits == reported
+}
+
+fn handle_move(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<MoveJournal>(shared, input_data, Command::Move, "move") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if let Some(player) = game.pmap.get_mut(&data.fleet) { //check if the fleet exists
+            if game.mode != GameMode::Movable { //ships only move in their own mode
+                let msg = format!("Ships can't move in game {}, it is played in {:?} mode", data.gameid, game.mode);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if game.next_player != Some(data.fleet.clone()) { //check if turn
+                let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if !game.pending_reports.is_empty() || game.pending_scan.is_some() { //the move is made instead of firing
+                let msg = format!("Must address report first");
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if player.current_state != data.board { //Check the move is made on the current board
+                let msg = format!("Player {} moved on the wrong board", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if !continues_move_history(player, &data, &methods) { //check the move extends the fleet's proven history
+                let msg = format!("Player {} moved with a broken board history", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if !moved_with_hits(player, &data) { //a hit ship stays where it is
+                let msg = format!("Player {} moved without counting the hits on their fleet", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if data.mines != player.mines { //check the move was checked against the mines placed at join
+                let msg = format!("Player {} moved without the mines placed at join", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else {
+                player.current_state = data.next_board;
+                player.reports = data.reports;
+                player.history = Some(input_data.receipt.clone());
+                player.moved_after.get_or_insert(player.reported.len());
+                game.started = true;
+
+                // Which ship moved and where stays with the fleet
+                let msg = format!("[{}] Player {} moved a ship", kind, data.fleet);
+                shared.publish(&data.gameid, msg);
+                game.next_player = game.next_after(&data.fleet);
+                pass_turn(shared, game, &data.gameid);
+            }
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+    "OK".to_string()
+}
+
+fn handle_wave(shared: &SharedData, input_data: &CommunicationData) -> String {
+    // TO DO:
+    let Some((data, kind, methods)) = verified_journal::<BaseJournal>(shared, input_data, Command::Wave, "wave") else {
+        return "Could not verify receipt".to_string();
+    };
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if game.pmap.contains_key(&data.fleet) { //check if the fleet exists
+            if !game.awaiting_answer() { // check if game has report
+                if game.next_player == Some(data.fleet.clone()) { //check if turn
+                    let msg = format!("[{}] Player {} waves their turn", kind, data.fleet);
+                    shared.publish(&data.gameid, msg);
+                    game.next_player = game.next_after(&data.fleet);
+                    pass_turn(shared, game, &data.gameid);
+                }
+                else {
+                    let msg = format!("Player {}, not your turn dummy!", data.fleet);
+                    shared.publish(&data.gameid, msg.clone());
+                    return msg;
+                }
+            }
+            else {
+                let msg = format!("Must address report first");
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+        }
+        else {
+            let msg = format!("Player {} does not exist in game {}", data.fleet, data.gameid);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+fn handle_win(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, methods)) = verified_journal::<BaseJournal>(shared, input_data, Command::Win, "win") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if let Some(player) = game.pmap.get_mut(&data.fleet) { //check if the fleet exists
+            if player.current_state != data.board { //Check if report is for the correct board
+                let msg = format!("Player {} claiming victory with the wrong board!!!", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if player.eliminated() { //a sunk fleet can't win
+                let msg = format!("Player {} is eliminated", data.fleet);
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else if game.order.iter().all(|f| game.allies(f, &data.fleet)) || game.alive().iter().any(|f| !game.allies(f, &data.fleet)) { //only the last fleet or team standing wins
+                let afloat: Vec<String> = game.alive().into_iter().filter(|f| !game.allies(f, &data.fleet)).collect();
+                let msg = format!("Player {} can't claim victory, still afloat: {}", data.fleet, afloat.join(", "));
+                shared.publish(&data.gameid, msg.clone());
+                return msg;
+            }
+            else {
+                game.winner = Some(data.fleet.clone());
+                game.next_player = None;
+                let mut msg = format!("[{}] Player {} claimed victory", kind, data.fleet);
+                if let Some(team) = &game.pmap[&data.fleet].team {
+                    msg.push_str(&format!(" for team {}", team));
+                }
+                shared.publish(&data.gameid, msg);
+                record_result(shared, game, &data.gameid);
+                if let Some(tournament) = game.tournament.clone() {
+                    record_match(shared, &mut gmap, &tournament, &data.gameid, &data.fleet);
+                }
+            }
+
+        }
+        else {
+            let msg = format!("Player {} not in game", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+// Takes back the draw on offer once a fleet fires or resigns
+fn withdraw_draw(shared: &SharedData, game: &mut Game, gameid: &str) {
+    if !game.draw.is_empty() {
+        game.draw.clear();
+        shared.publish(gameid, format!("The draw offered in game {} is withdrawn", gameid));
+    }
+}
+
+// Why the fleet can't resign or take part in a draw, if it can't. Every ownership
+// receipt verifies against the same method, so its journal names the action.
+fn ownership_refusal(game: &Game, data: &OwnershipJournal, cmd: Command) -> Option<String> {
+    if let Some(msg) = game.over(&data.gameid) { //nothing happens once the game is over
+        return Some(msg);
+    }
+    let Some(player) = game.pmap.get(&data.fleet) else { //check if the fleet exists
+        return Some(format!("Player {} not in game", data.fleet));
+    };
+    if data.action != cmd { //a receipt for one action is no good for another
+        Some(format!("Receipt of player {} is for {:?}, not {:?}", data.fleet, data.action, cmd))
+    }
+    else if player.current_state != data.board { //only the fleet itself acts
+        Some(format!("Player {} sent {:?} with the wrong board", data.fleet, cmd))
+    }
+    else if player.eliminated() { //a sunk fleet has no say
+        Some(format!("Player {} is eliminated", data.fleet))
+    }
+    else {
+        None
+    }
+}
+
+fn handle_resign(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, _)) = verified_journal::<OwnershipJournal>(shared, input_data, Command::Resign, "resign") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = ownership_refusal(game, &data, Command::Resign) { //only a fleet afloat resigns
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        game.pmap.get_mut(&data.fleet).unwrap().resigned = true;
+        shared.publish(&data.gameid, format!("[{}] Player {} resigned from game {}", kind, data.fleet, data.gameid));
+        shared.publish(&data.gameid, format!("Player {} eliminated from game {}", data.fleet, data.gameid));
+        withdraw_draw(shared, game, &data.gameid);
+
+        let mut turn_passed = false;
+        if game.next_player == Some(data.fleet.clone()) { //the turn moves on from a fleet that resigns on it
+            if game.awaiting_answer() { //shots and scans at the fleet go unanswered
+                game.pending_reports.clear();
+                game.pending_scan = None;
+                game.shots.retain(|shot| shot.result.is_some());
+                let shooter = game.shooter.take().unwrap_or(data.fleet.clone());
+                game.next_player = game.next_after(&shooter);
+            }
+            else {
+                game.next_player = game.next_after(&data.fleet);
+            }
+            turn_passed = true;
+        }
+        let alive = game.alive();
+        if let Some(winner) = alive.first().filter(|first| alive.iter().all(|f| game.allies(first, f))) { //only allies are left, the game is theirs
+            game.winner = Some(winner.clone());
+            game.next_player = None;
+            shared.publish(&data.gameid, game.over(&data.gameid).unwrap());
+            record_result(shared, game, &data.gameid);
+            if let Some(tournament) = game.tournament.clone() {
+                record_match(shared, &mut gmap, &tournament, &data.gameid, winner);
+            }
+        }
+        else if turn_passed {
+            pass_turn(shared, game, &data.gameid);
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+fn handle_offer_draw(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, _)) = verified_journal::<OwnershipJournal>(shared, input_data, Command::OfferDraw, "offer a draw") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = ownership_refusal(game, &data, Command::OfferDraw) { //only a fleet afloat offers
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if game.tournament.is_some() { //a tournament match needs a winner
+            let msg = format!("Game {} is a tournament match, it can't be drawn", data.gameid);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else if !game.draw.is_empty() { //one offer at a time
+            let msg = format!("Player {} already offered a draw in game {}", game.draw[0], data.gameid);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else if data.offer != game.draw_offers + 1 { //an old offer can't be made again
+            let msg = format!("Player {} made draw offer {}, the next one in game {} is {}", data.fleet, data.offer, data.gameid, game.draw_offers + 1);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else if game.alive().iter().all(|f| game.allies(f, &data.fleet)) { //the last fleet or team standing claims victory instead
+            let msg = format!("Player {} can claim victory, there is nobody left to draw with", data.fleet);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else {
+            game.draw = vec![data.fleet.clone()];
+            game.draw_offers = data.offer;
+            let msg = format!("[{}] Player {} offered a draw in game {}", kind, data.fleet, data.gameid);
+            shared.publish(&data.gameid, msg);
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+fn handle_accept_draw(shared: &SharedData, input_data: &CommunicationData) -> String {
+    let Some((data, kind, _)) = verified_journal::<OwnershipJournal>(shared, input_data, Command::AcceptDraw, "accept a draw") else {
+        return "Could not verify receipt".to_string();
+    };
+
+    let mut gmap = shared.gmap.lock().unwrap();
+    if let Some(game) = gmap.get_mut(&data.gameid) { //get the game with game id
+        if let Some(msg) = ownership_refusal(game, &data, Command::AcceptDraw) { //only a fleet afloat accepts
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        if game.draw.is_empty() { //there must be an offer to accept
+            let msg = format!("No draw offered in game {}", data.gameid);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else if data.offer != game.draw_offers { //only the offer on the table can be accepted
+            let msg = format!("Player {} accepted draw offer {}, the one in game {} is {}", data.fleet, data.offer, data.gameid, game.draw_offers);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else if game.draw.contains(&data.fleet) { //each fleet agrees once
+            let msg = format!("Player {} already agreed to the draw in game {}", data.fleet, data.gameid);
+            shared.publish(&data.gameid, msg.clone());
+            return msg;
+        }
+        else {
+            game.draw.push(data.fleet.clone());
+            let msg = format!("[{}] Player {} accepted the draw in game {}", kind, data.fleet, data.gameid);
+            shared.publish(&data.gameid, msg);
+            if game.alive().iter().all(|f| game.draw.contains(f)) { //the game ends once every fleet afloat agreed
+                game.drawn = true;
+                game.next_player = None;
+                game.pending_reports.clear();
+                game.pending_scan = None;
+                shared.publish(&data.gameid, format!("Game {} ended in a draw", data.gameid));
+                record_result(shared, game, &data.gameid);
+            }
+        }
+    }
+    else {
+        let msg = format!("Game {} does not exist", data.gameid);
+        shared.publish(&data.gameid, msg.clone());
+        return msg;
+    }
+
+    "OK".to_string()
+}
+
+// Opens a fleet's join commitments once its game is over and checks every report the
+// fleet made on the board it joined with. Needs no receipt, only the fleet knows the
+// nonce that opens them.
+fn handle_reveal(shared: &SharedData, reveal: &RevealData) -> String {
+    let reject = |msg: String| {
+        shared.publish(&reveal.gameid, msg.clone());
+        msg
+    };
+    let mut gmap = shared.gmap.lock().unwrap();
+    let Some(game) = gmap.get_mut(&reveal.gameid) else {
+        return reject(format!("Game {} does not exist", reveal.gameid));
+    };
+    if game.winner.is_none() && !game.drawn { //boards stay hidden while the game is played
+        return reject(format!("Game {} is not over, fleets are revealed once it is", reveal.gameid));
+    }
+    let Some(player) = game.pmap.get_mut(&reveal.fleet) else {
+        return reject(format!("Player {} not in game", reveal.fleet));
+    };
+    if player.revealed.is_some() {
+        return reject(format!("Player {} already revealed their fleet", reveal.fleet));
+    }
+    if commitment(&reveal.random, &reveal.board) != player.origin { //must open the commitment made at join
+        return reject(format!("Player {} revealed a board that does not match their join", reveal.fleet));
+    }
+    let mines_match = match player.mines {
+        Some(mines) => commitment(&reveal.random, &reveal.mines) == mines,
+        None => reveal.mines.is_empty(),
+    };
+    if !mines_match {
+        return reject(format!("Player {} revealed mines that do not match their join", reveal.fleet));
+    }
+
+    let checked = player.moved_after.unwrap_or(player.reported.len());
+    let misreports: Vec<String> = player.reported[..checked]
+        .iter()
+        .filter(|(pos, report)| {
+            let outcome = if reveal.board.contains(pos) {
+                "Hit"
+            } else if reveal.mines.contains(pos) {
+                "Mine"
+            } else {
+                "Miss"
+            };
+            report != outcome
+        })
+        .map(|(pos, report)| format!("{} reported {}", xy_pos(*pos), report))
+        .collect();
+    let unchecked = player.reported.len() - checked;
+
+    let mut msg = format!("Player {} revealed their fleet in game {}", reveal.fleet, reveal.gameid);
+    if misreports.is_empty() {
+        msg.push_str(&format!(", {} reports check out", checked));
+    } else {
+        msg.push_str(&format!(", misreported {}", misreports.join(", ")));
+    }
+    if unchecked > 0 {
+        msg.push_str(&format!(", {} reports after a ship moved are not checked", unchecked));
+    }
+    player.revealed = Some(Revealed {
+        board: reveal.board.iter().map(|&pos| xy_pos(pos)).collect(),
+        mines: reveal.mines.iter().map(|&pos| xy_pos(pos)).collect(),
+        misreports,
+        unchecked,
+    });
+    shared.publish(&reveal.gameid, msg);
+    "OK".to_string()
+}
@@ -0,0 +1,204 @@
+// Elo ratings of fleets, recomputed from the results of the games the node saw end.
+// Fleets are identified by name across games. A node that accepts dev games rates them
+// on a leaderboard of their own, since anyone can win one with fake receipts.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+// Most a rating can move in one game
+const K_FACTOR: f64 = 32.0;
+
+// A finished game, known from the accepted victory claim or draw that ended it
+#[derive(Clone, Serialize)]
+pub struct GameResult {
+    pub gameid: String,
+    // The fleet that claimed victory and its teammates, or the fleets afloat that
+    // agreed to a draw
+    pub winners: Vec<String>,
+    // Every other fleet of the game, in the order they joined
+    pub losers: Vec<String>,
+    // Fleets of different teams that drew with each other, empty for a game that was won
+    pub draws: Vec<(String, String)>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct RatingChange {
+    pub gameid: String,
+    pub won: bool,
+    pub drawn: bool,
+    pub opponents: Vec<String>,
+    pub change: f64,
+    // Rating after the game
+    pub rating: f64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PlayerRating {
+    pub fleet: String,
+    pub rating: f64,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub history: Vec<RatingChange>,
+}
+
+// Plays the results in order from the initial rating. Every winner beats every loser
+// in a one on one game weighted by the number of losers, so a two fleet game is
+// plain Elo and no game moves a winner by more than K_FACTOR. Fleets that drew score
+// half a game against each other, weighted by the number of pairs that drew.
+pub fn ratings(results: &[GameResult]) -> HashMap<String, PlayerRating> {
+    let mut players: HashMap<String, PlayerRating> = HashMap::new();
+    for result in results.iter().filter(|r| !r.losers.is_empty() || !r.draws.is_empty()) {
+        let rating = |fleet: &str| players.get(fleet).map_or(INITIAL_RATING, |p| p.rating);
+        let expected = |fleet: &str, opponent: &str| 1.0 / (1.0 + 10f64.powf((rating(opponent) - rating(fleet)) / 400.0));
+
+        // Changes are worked out on the ratings from before the game
+        let mut changes: HashMap<&str, f64> = HashMap::new();
+        for winner in &result.winners {
+            for loser in &result.losers {
+                let change = K_FACTOR / result.losers.len() as f64 * (1.0 - expected(winner, loser));
+                *changes.entry(winner).or_default() += change;
+                *changes.entry(loser).or_default() -= change;
+            }
+        }
+        for (fleet, other) in &result.draws {
+            let change = K_FACTOR / result.draws.len() as f64 * (0.5 - expected(fleet, other));
+            *changes.entry(fleet).or_default() += change;
+            *changes.entry(other).or_default() -= change;
+        }
+
+        let drawn = !result.draws.is_empty();
+        let change = |fleet: &String| changes.get(fleet.as_str()).copied().unwrap_or_default();
+        for winner in &result.winners {
+            let mut opponents = result.losers.clone();
+            for (fleet, other) in &result.draws {
+                if fleet == winner {
+                    opponents.push(other.clone());
+                } else if other == winner {
+                    opponents.push(fleet.clone());
+                }
+            }
+            apply(&mut players, winner, &result.gameid, !drawn, drawn, opponents, change(winner));
+        }
+        for loser in &result.losers {
+            apply(&mut players, loser, &result.gameid, false, false, result.winners.clone(), change(loser));
+        }
+    }
+    players
+}
+
+fn apply(
+    players: &mut HashMap<String, PlayerRating>,
+    fleet: &str,
+    gameid: &str,
+    won: bool,
+    drawn: bool,
+    opponents: Vec<String>,
+    change: f64,
+) {
+    let player = players.entry(fleet.to_string()).or_insert_with(|| PlayerRating {
+        fleet: fleet.to_string(),
+        rating: INITIAL_RATING,
+        games: 0,
+        wins: 0,
+        draws: 0,
+        history: Vec::new(),
+    });
+    player.rating += change;
+    player.games += 1;
+    player.wins += won as u32;
+    player.draws += drawn as u32;
+    player.history.push(RatingChange {
+        gameid: gameid.to_string(),
+        won,
+        drawn,
+        opponents,
+        change,
+        rating: player.rating,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(gameid: &str, winners: &[&str], losers: &[&str], draws: &[(&str, &str)]) -> GameResult {
+        GameResult {
+            gameid: gameid.to_string(),
+            winners: winners.iter().map(|f| f.to_string()).collect(),
+            losers: losers.iter().map(|f| f.to_string()).collect(),
+            draws: draws.iter().map(|(f, o)| (f.to_string(), o.to_string())).collect(),
+        }
+    }
+
+    fn expected(rating: f64, opponent: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+    }
+
+    fn assert_close(actual: f64, wanted: f64) {
+        assert!((actual - wanted).abs() < 1e-9, "{} is not {}", actual, wanted);
+    }
+
+    #[test]
+    fn two_fleets_play_plain_elo() {
+        let rated = ratings(&[result("g1", &["a"], &["b"], &[])]);
+        assert_close(rated["a"].rating, 1516.0);
+        assert_close(rated["b"].rating, 1484.0);
+        assert_eq!((rated["a"].games, rated["a"].wins), (1, 1));
+        assert_eq!((rated["b"].games, rated["b"].wins), (1, 0));
+    }
+
+    #[test]
+    fn free_for_all_splits_the_k_factor() {
+        let rated = ratings(&[result("g1", &["a"], &["b", "c", "d"], &[])]);
+        // The winner gains no more than in a two fleet game, each loser gives up a third
+        assert_close(rated["a"].rating, 1516.0);
+        assert_eq!(rated["a"].history[0].opponents, ["b", "c", "d"]);
+        for loser in ["b", "c", "d"] {
+            assert_close(rated[loser].rating, 1500.0 - 16.0 / 3.0);
+            assert_eq!(rated[loser].history[0].opponents, ["a"]);
+            assert_eq!(rated[loser].wins, 0);
+        }
+    }
+
+    #[test]
+    fn teammates_share_the_result() {
+        let rated = ratings(&[result("g1", &["a", "b"], &["c", "d"], &[])]);
+        for winner in ["a", "b"] {
+            assert_close(rated[winner].rating, 1516.0);
+            assert_eq!(rated[winner].wins, 1);
+        }
+        for loser in ["c", "d"] {
+            assert_close(rated[loser].rating, 1484.0);
+        }
+    }
+
+    #[test]
+    fn draws_are_weighted_by_pairs() {
+        // Evenly rated fleets that draw keep their ratings
+        let pairs = [("a", "b"), ("a", "c"), ("b", "c")];
+        let even = ratings(&[result("g1", &["a", "b", "c"], &[], &pairs)]);
+        for fleet in ["a", "b", "c"] {
+            assert_close(even[fleet].rating, INITIAL_RATING);
+            assert_eq!((even[fleet].games, even[fleet].wins, even[fleet].draws), (1, 0, 1));
+            assert!(even[fleet].history[0].drawn);
+        }
+
+        // After a win the favourite gives points back on a draw, a share for each pair
+        let rated = ratings(&[result("g1", &["a"], &["b"], &[]), result("g2", &["a", "b", "c"], &[], &pairs)]);
+        let share = K_FACTOR / pairs.len() as f64;
+        let a = 1516.0 + share * (0.5 - expected(1516.0, 1484.0)) + share * (0.5 - expected(1516.0, 1500.0));
+        let c = 1500.0 + share * (0.5 - expected(1500.0, 1516.0)) + share * (0.5 - expected(1500.0, 1484.0));
+        assert_close(rated["a"].rating, a);
+        assert_close(rated["c"].rating, c);
+        let total: f64 = ["a", "b", "c"].iter().map(|f| rated[*f].rating).sum();
+        assert_close(total, 3.0 * INITIAL_RATING);
+        assert_eq!(rated["c"].history[0].opponents, ["a", "b"]);
+    }
+
+    #[test]
+    fn games_without_opponents_are_not_rated() {
+        assert!(ratings(&[result("g1", &["a"], &[], &[])]).is_empty());
+    }
+}
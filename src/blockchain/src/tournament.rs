@@ -0,0 +1,320 @@
+// Tournaments between registered fleets. The node draws the matches and creates
+// their games, players then join and play them like any other game, and the
+// accepted victory claim of a match is its result.
+
+use fleetcore::GameMode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Format {
+    // Every fleet meets every other fleet once
+    RoundRobin,
+    // Winners go on to the next round until one is left
+    SingleElimination,
+}
+
+// Transactions that run a tournament, posted to /tournaments as JSON tagged with
+// their action, e.g. {"action":"Register","tournament":"cup","fleet":"alice"}
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "action")]
+pub enum TournamentCommand {
+    Create {
+        tournament: String,
+        format: Format,
+        #[serde(default)]
+        mode: GameMode,
+        // Played with fake receipts, only on a node that accepts dev games
+        #[serde(default)]
+        dev: bool,
+    },
+    Register {
+        tournament: String,
+        fleet: String,
+    },
+    // Closes registration and draws the first matches
+    Start {
+        tournament: String,
+    },
+}
+
+#[derive(Clone, Serialize)]
+pub struct Match {
+    // Game the match is played in, none for a fleet that goes through on a bye
+    pub gameid: Option<String>,
+    pub round: u32,
+    pub fleets: Vec<String>,
+    pub winner: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TournamentStanding {
+    pub fleet: String,
+    pub played: u32,
+    pub wins: u32,
+    // Knocked out of a single elimination tournament
+    pub out: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Tournament {
+    pub id: String,
+    pub format: Format,
+    pub mode: GameMode,
+    // Its matches are dev games, any other tournament refuses fake receipts
+    pub dev: bool,
+    // Registered fleets, their order seeds the draw
+    pub fleets: Vec<String>,
+    pub matches: Vec<Match>,
+    pub started: bool,
+    pub finished: bool,
+    // None while the tournament is played, or when a round robin ends in a tie
+    pub champion: Option<String>,
+}
+
+impl Tournament {
+    pub fn new(id: &str, format: Format, mode: GameMode, dev: bool) -> Self {
+        Tournament {
+            id: id.to_string(),
+            format,
+            mode,
+            dev,
+            fleets: Vec::new(),
+            matches: Vec::new(),
+            started: false,
+            finished: false,
+            champion: None,
+        }
+    }
+
+    // Prefix of the ids of the tournament's games, no other game may use it
+    pub fn game_prefix(&self) -> String {
+        format!("{}-r", self.id)
+    }
+
+    // Draws every round of a round robin, or the first round of a single elimination.
+    // Returns the new matches that need a game.
+    pub fn start(&mut self) -> Vec<Match> {
+        self.started = true;
+        let pairs = match self.format {
+            Format::RoundRobin => round_robin(&self.fleets),
+            Format::SingleElimination => knockout_round(1, &self.fleets),
+        };
+        self.add_matches(pairs)
+    }
+
+    // Records the winner of the match played in the game, if it is one of ours.
+    // Returns the matches drawn because a round was completed.
+    pub fn record(&mut self, gameid: &str, winner: &str) -> Vec<Match> {
+        let Some(played) = self.matches.iter_mut().find(|m| m.gameid.as_deref() == Some(gameid)) else {
+            return Vec::new();
+        };
+        played.winner = Some(winner.to_string());
+        let round = played.round;
+
+        if self.matches.iter().any(|m| m.winner.is_none()) {
+            return Vec::new();
+        }
+        match self.format {
+            Format::RoundRobin => {
+                let standings = self.standings();
+                let leaders: Vec<&TournamentStanding> = standings.iter().filter(|s| s.wins == standings[0].wins).collect();
+                if leaders.len() == 1 {
+                    self.champion = Some(leaders[0].fleet.clone());
+                }
+                self.finished = true;
+                Vec::new()
+            }
+            Format::SingleElimination => {
+                let winners: Vec<String> = self
+                    .matches
+                    .iter()
+                    .filter(|m| m.round == round)
+                    .filter_map(|m| m.winner.clone())
+                    .collect();
+                if winners.len() == 1 {
+                    self.champion = Some(winners[0].clone());
+                    self.finished = true;
+                    return Vec::new();
+                }
+                let pairs = knockout_round(round + 1, &winners);
+                self.add_matches(pairs)
+            }
+        }
+    }
+
+    // Fleets still in first, then most wins, then fewest games played, then seeding
+    pub fn standings(&self) -> Vec<TournamentStanding> {
+        let mut standings: Vec<TournamentStanding> = self
+            .fleets
+            .iter()
+            .map(|fleet| {
+                let games = self.matches.iter().filter(|m| m.gameid.is_some() && m.fleets.contains(fleet));
+                let decided: Vec<&Match> = games.filter(|m| m.winner.is_some()).collect();
+                let wins = decided.iter().filter(|m| m.winner.as_ref() == Some(fleet)).count() as u32;
+                TournamentStanding {
+                    fleet: fleet.clone(),
+                    played: decided.len() as u32,
+                    wins,
+                    out: self.format == Format::SingleElimination && wins < decided.len() as u32,
+                }
+            })
+            .collect();
+        standings.sort_by(|a, b| a.out.cmp(&b.out).then(b.wins.cmp(&a.wins)).then(a.played.cmp(&b.played)));
+        standings
+    }
+
+    fn add_matches(&mut self, pairs: Vec<(u32, Vec<String>)>) -> Vec<Match> {
+        let mut added = Vec::new();
+        for (round, fleets) in pairs {
+            let number = self.matches.iter().filter(|m| m.round == round && m.gameid.is_some()).count() + 1;
+            let new_match = if fleets.len() == 1 {
+                Match { gameid: None, round, winner: Some(fleets[0].clone()), fleets }
+            } else {
+                let gameid = format!("{}{}-m{}", self.game_prefix(), round, number);
+                Match { gameid: Some(gameid), round, fleets, winner: None }
+            };
+            self.matches.push(new_match.clone());
+            added.push(new_match);
+        }
+        added
+    }
+}
+
+// Pairs the fleets in seeding order, a fleet left over goes through on a bye
+fn knockout_round(round: u32, fleets: &[String]) -> Vec<(u32, Vec<String>)> {
+    fleets.chunks(2).map(|pair| (round, pair.to_vec())).collect()
+}
+
+// Circle method: the first seat stays while the others turn round one place each
+// round, an odd field gets an empty seat and whoever meets it sits the round out
+fn round_robin(fleets: &[String]) -> Vec<(u32, Vec<String>)> {
+    let mut seats: Vec<Option<&String>> = fleets.iter().map(Some).collect();
+    if seats.len() % 2 == 1 {
+        seats.push(None);
+    }
+    let n = seats.len();
+    let mut pairs = Vec::new();
+    for round in 1..n as u32 {
+        for i in 0..n / 2 {
+            if let (Some(a), Some(b)) = (seats[i], seats[n - 1 - i]) {
+                pairs.push((round, vec![a.clone(), b.clone()]));
+            }
+        }
+        seats[1..].rotate_right(1);
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fleets(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("f{}", i)).collect()
+    }
+
+    fn started(format: Format, count: usize) -> Tournament {
+        let mut tournament = Tournament::new("cup", format, GameMode::default(), false);
+        tournament.fleets = fleets(count);
+        tournament.start();
+        tournament
+    }
+
+    #[test]
+    fn every_pair_meets_once() {
+        for count in 2..=9 {
+            let pairs = round_robin(&fleets(count));
+            assert_eq!(pairs.len(), count * (count - 1) / 2, "{} fleets", count);
+            let mut met: Vec<(String, String)> = pairs
+                .iter()
+                .map(|(_, p)| if p[0] < p[1] { (p[0].clone(), p[1].clone()) } else { (p[1].clone(), p[0].clone()) })
+                .collect();
+            met.sort();
+            met.dedup();
+            assert_eq!(met.len(), pairs.len(), "{} fleets", count);
+        }
+    }
+
+    #[test]
+    fn odd_fields_sit_one_fleet_out_each_round() {
+        let pairs = round_robin(&fleets(5));
+        let rounds = pairs.iter().map(|(round, _)| *round).max().unwrap();
+        assert_eq!(rounds, 5);
+        let mut resting = Vec::new();
+        for round in 1..=rounds {
+            let playing: Vec<&String> = pairs.iter().filter(|(r, _)| *r == round).flat_map(|(_, p)| p).collect();
+            assert_eq!(playing.len(), 4, "round {}", round);
+            let out: Vec<String> = fleets(5).into_iter().filter(|f| !playing.contains(&f)).collect();
+            assert_eq!(out.len(), 1, "round {}", round);
+            resting.push(out[0].clone());
+        }
+        resting.sort();
+        assert_eq!(resting, fleets(5));
+    }
+
+    #[test]
+    fn even_fields_play_every_fleet_every_round() {
+        let pairs = round_robin(&fleets(4));
+        for round in 1..=3 {
+            let mut playing: Vec<&String> = pairs.iter().filter(|(r, _)| *r == round).flat_map(|(_, p)| p).collect();
+            playing.sort();
+            playing.dedup();
+            assert_eq!(playing.len(), 4, "round {}", round);
+        }
+    }
+
+    #[test]
+    fn round_robin_games_are_numbered_per_round() {
+        let tournament = started(Format::RoundRobin, 3);
+        let ids: Vec<&str> = tournament.matches.iter().filter_map(|m| m.gameid.as_deref()).collect();
+        assert_eq!(ids, ["cup-r1-m1", "cup-r2-m1", "cup-r3-m1"]);
+    }
+
+    #[test]
+    fn sole_leader_wins_the_round_robin() {
+        let mut tournament = started(Format::RoundRobin, 3);
+        for m in tournament.matches.clone() {
+            let winner = if m.fleets.contains(&"f0".to_string()) { "f0" } else { "f1" };
+            tournament.record(m.gameid.as_deref().unwrap(), winner);
+        }
+        assert!(tournament.finished);
+        assert_eq!(tournament.champion.as_deref(), Some("f0"));
+        let standings = tournament.standings();
+        let order: Vec<&str> = standings.iter().map(|s| s.fleet.as_str()).collect();
+        assert_eq!(order, ["f0", "f1", "f2"]);
+    }
+
+    #[test]
+    fn tied_leaders_leave_no_champion() {
+        let mut tournament = started(Format::RoundRobin, 3);
+        // Every fleet beats one other and loses to the third
+        for m in tournament.matches.clone() {
+            let winner = match (m.fleets[0].as_str(), m.fleets[1].as_str()) {
+                ("f0", "f1") | ("f1", "f0") => "f0",
+                ("f1", "f2") | ("f2", "f1") => "f1",
+                _ => "f2",
+            };
+            tournament.record(m.gameid.as_deref().unwrap(), winner);
+        }
+        assert!(tournament.finished);
+        assert_eq!(tournament.champion, None);
+        let standings = tournament.standings();
+        assert!(standings.iter().all(|s| s.wins == 1 && s.played == 2));
+        // Even standings keep the seeding order
+        let order: Vec<&str> = standings.iter().map(|s| s.fleet.as_str()).collect();
+        assert_eq!(order, ["f0", "f1", "f2"]);
+    }
+
+    #[test]
+    fn unfinished_round_robin_has_no_champion() {
+        let mut tournament = started(Format::RoundRobin, 3);
+        // f0 sits out the first round, which f1 wins against f2
+        tournament.record("cup-r1-m1", "f1");
+        assert!(!tournament.finished);
+        assert_eq!(tournament.champion, None);
+        // Fewer games played ranks first among fleets on the same wins
+        let standings = tournament.standings();
+        let order: Vec<&str> = standings.iter().map(|s| s.fleet.as_str()).collect();
+        assert_eq!(order, ["f1", "f0", "f2"]);
+    }
+}
@@ -0,0 +1,89 @@
+// Game transcripts, every accepted move of one game in the order the node applied it,
+// served by /games/:id/transcript for replays and audits. The JSON looks like
+//
+//   {"gameid":"g1","mode":"Classic","protocol":9,"dev":false,"tournament":null,"seats":[],
+//    "teams":{},"winner":"alice","drawn":false,
+//    "moves":[{"index":0,"time":1700000000000,"cmd":"Join","mode":null,"fleet":"alice",
+//              "image_id":[...],"journal":{"gameid":"g1","fleet":"alice",...},
+//              "events":["[succinct] Player alice joined game g1"]}, ...]}
+//
+// where a move's journal is the journal of its command (BaseJournal for Join, Wave and Win,
+// OwnershipJournal for Resign and the draw commands, FireJournal for Fire, and so on) and its events are the
+// messages the node published about the game when it applied the move. With
+// ?receipts=true every move also carries the receipt it was posted with, so the game
+// can be verified again.
+
+use fleetcore::{
+    BaseJournal, BatchReportJournal, Command, CommunicationData, FireJournal, GameMode, MoveJournal, OwnershipJournal, ReportJournal,
+    ScanJournal, ScanReportJournal,
+};
+use risc0_zkvm::{Digest, Receipt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Serialize)]
+pub struct Transcript {
+    pub gameid: String,
+    pub mode: GameMode,
+    // Protocol version of the methods the game was played with
+    pub protocol: u32,
+    // Played with fake receipts, so it proves nothing
+    pub dev: bool,
+    // Tournament the game is a match of, only the fleets drawn for it could join
+    #[serde(default)]
+    pub tournament: Option<String>,
+    #[serde(default)]
+    pub seats: Vec<String>,
+    // Fleets of each team, empty for a game without teams
+    #[serde(default)]
+    pub teams: BTreeMap<String, Vec<String>>,
+    // Fleet that claimed victory, none while the game is played
+    pub winner: Option<String>,
+    // Every fleet afloat agreed to a draw
+    #[serde(default)]
+    pub drawn: bool,
+    pub moves: Vec<TranscriptMove>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TranscriptMove {
+    // Position of the move among all the transactions the node accepted
+    pub index: usize,
+    // Milliseconds since the Unix epoch when the node accepted it, 0 if it was
+    // logged before the node kept the time
+    pub time: u64,
+    pub cmd: Command,
+    // Mode the join asked for, as posted
+    pub mode: Option<GameMode>,
+    pub fleet: String,
+    // Image ID of the method the receipt was verified against
+    pub image_id: Digest,
+    pub journal: serde_json::Value,
+    pub events: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+}
+
+// The move's journal decoded for its command, none if it does not decode
+pub(crate) fn journal_json(data: &CommunicationData) -> Option<serde_json::Value> {
+    let journal = &data.receipt.journal;
+    let json = match data.cmd {
+        Command::Join | Command::Wave | Command::Win => serde_json::to_value(journal.decode::<BaseJournal>().ok()?),
+        Command::Resign | Command::OfferDraw | Command::AcceptDraw => {
+            serde_json::to_value(journal.decode::<OwnershipJournal>().ok()?)
+        }
+        Command::Fire => serde_json::to_value(journal.decode::<FireJournal>().ok()?),
+        Command::Report => serde_json::to_value(journal.decode::<ReportJournal>().ok()?),
+        Command::BatchReport => serde_json::to_value(journal.decode::<BatchReportJournal>().ok()?),
+        Command::Scan => serde_json::to_value(journal.decode::<ScanJournal>().ok()?),
+        Command::ScanReport => serde_json::to_value(journal.decode::<ScanReportJournal>().ok()?),
+        Command::Move => serde_json::to_value(journal.decode::<MoveJournal>().ok()?),
+    };
+    json.ok()
+}
+
+// Game and fleet the move is for, every journal starts with them
+pub(crate) fn move_of(data: &CommunicationData) -> Option<(String, String)> {
+    let journal = journal_json(data)?;
+    Some((journal["gameid"].as_str()?.to_string(), journal["fleet"].as_str()?.to_string()))
+}
@@ -0,0 +1,419 @@
+// In-process harness for end-to-end games.
+//
+// Starts one chain node on an ephemeral port for the whole test binary and drives
+// players through the host library with dev mode receipts. The host finds the node
+// through CHAIN_URL, which is process wide, so every test shares the node and keeps
+// to its own game and fleet names.
+
+use blockchain::{app, DevPolicy, SharedData};
+use fleetcore::{Command, CommunicationData, Direction, GameMode};
+use host::FormData;
+use futures::StreamExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+pub struct Chain {
+    pub url: String,
+    shared: SharedData,
+    // The node's transaction log
+    log: PathBuf,
+}
+
+static CHAIN: OnceLock<Chain> = OnceLock::new();
+
+// Restarted nodes so far, each gets a log of its own
+static RESTARTS: AtomicUsize = AtomicUsize::new(0);
+
+// Starts the node on first use. It runs on a thread of its own, since every
+// #[tokio::test] has its own runtime that ends with the test.
+pub fn chain() -> &'static Chain {
+    CHAIN.get_or_init(|| {
+        // Set before any test proves or submits anything
+        std::env::set_var("RISC0_DEV_MODE", "1");
+        let receipts = std::env::temp_dir().join(format!("e2e-receipts-{}", std::process::id()));
+        std::env::set_var("RECEIPTS_DIR", &receipts);
+
+        let log = std::env::temp_dir().join(format!("e2e-chain-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let chain = Chain::start(log);
+        std::env::set_var("CHAIN_URL", &chain.url);
+        chain
+    })
+}
+
+impl Chain {
+    fn start(log: PathBuf) -> Chain {
+        let shared = SharedData::open(DevPolicy::DevGames, Some(log.clone())).expect("Chain node did not start");
+        let router = app(shared.clone());
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                tx.send(listener.local_addr().unwrap()).unwrap();
+                axum::serve(listener, router).await.unwrap();
+            });
+        });
+        let addr = rx.recv().expect("Chain node did not start");
+        Chain { url: format!("http://{}", addr), shared, log }
+    }
+
+    // A second node started from a copy of this node's transaction log, as if this
+    // one was restarted. Hosts keep talking to the first node.
+    pub fn restarted(&self) -> Chain {
+        self.restarted_with("")
+    }
+
+    // Restarted from the log with the text added at its end, say a line cut short
+    pub fn restarted_with(&self, appended: &str) -> Chain {
+        let n = RESTARTS.fetch_add(1, Ordering::Relaxed);
+        let copy = self.log.with_extension(format!("restarted-{}.log", n));
+        let mut log = std::fs::read(&self.log).unwrap();
+        log.extend_from_slice(appended.as_bytes());
+        std::fs::write(&copy, log).unwrap();
+        Chain::start(copy)
+    }
+
+    // Log messages published from now on
+    pub fn events(&self) -> Events {
+        Events {
+            rx: self.shared.subscribe(),
+            pending: Vec::new(),
+        }
+    }
+
+    // Game state as returned by the game API, null if the game does not exist
+    pub async fn game(&self, gameid: &str) -> serde_json::Value {
+        self.get(&format!("/games/{}", gameid)).await
+    }
+
+    // The game's transcript, with every move's receipt if asked for
+    pub async fn transcript(&self, gameid: &str, receipts: bool) -> serde_json::Value {
+        self.get(&format!("/games/{}/transcript?receipts={}", gameid, receipts)).await
+    }
+
+    // Leaderboard of the dev games, which every test game is
+    pub async fn leaderboard(&self) -> serde_json::Value {
+        self.get("/leaderboard?dev=true").await
+    }
+
+    // Leaderboard of the games played with real receipts
+    pub async fn real_leaderboard(&self) -> serde_json::Value {
+        self.get("/leaderboard").await
+    }
+
+    // A fleet's rating and rated dev games, null if it has none
+    pub async fn player(&self, fleet: &str) -> serde_json::Value {
+        self.get(&format!("/players/{}?dev=true", fleet)).await
+    }
+
+    // Posts a tournament transaction, e.g. {"action":"Start","tournament":"cup"}
+    pub async fn tournament_action(&self, command: serde_json::Value) -> String {
+        let response = reqwest::Client::new()
+            .post(format!("{}/tournaments", self.url))
+            .json(&command)
+            .send()
+            .await
+            .unwrap();
+        response.text().await.unwrap()
+    }
+
+    pub async fn tournament(&self, id: &str) -> serde_json::Value {
+        self.get(&format!("/tournaments/{}", id)).await
+    }
+
+    pub async fn standings(&self, id: &str) -> serde_json::Value {
+        self.get(&format!("/tournaments/{}/standings", id)).await
+    }
+
+    async fn get(&self, path: &str) -> serde_json::Value {
+        let response = reqwest::get(format!("{}{}", self.url, path)).await.unwrap();
+        if !response.status().is_success() {
+            return serde_json::Value::Null;
+        }
+        response.json().await.unwrap()
+    }
+
+    // The first events of the game's spectator stream as (id, message), after the
+    // given event id if any
+    pub async fn game_events(&self, gameid: &str, last_event_id: Option<usize>, count: usize) -> Vec<(usize, String)> {
+        let mut request = reqwest::Client::new().get(format!("{}/games/{}/events", self.url, gameid));
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id.to_string());
+        }
+        let mut stream = request.send().await.unwrap().error_for_status().unwrap().bytes_stream();
+
+        let read = async {
+            let (mut events, mut buffer, mut id) = (Vec::new(), String::new(), None);
+            while events.len() < count {
+                let chunk = stream.next().await.expect("Stream closed").unwrap();
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                while let Some(end) = buffer.find('\n') {
+                    let line: String = buffer.drain(..=end).collect();
+                    let line = line.trim_end();
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = value.trim().parse::<usize>().ok();
+                    } else if let Some(msg) = line.strip_prefix("data:") {
+                        events.push((id.take().expect("Event without id"), msg.trim_start().to_string()));
+                    }
+                }
+            }
+            events.truncate(count);
+            events
+        };
+        tokio::time::timeout(Duration::from_secs(10), read)
+            .await
+            .expect("Timed out waiting for game events")
+    }
+}
+
+pub struct Events {
+    rx: broadcast::Receiver<String>,
+    // Received but not yet matched by expect
+    pending: Vec<String>,
+}
+
+impl Events {
+    // Messages not yet matched. The host only returns once the node answered,
+    // so the messages of a finished move are already here.
+    pub fn drain(&mut self) -> Vec<String> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(msg) => self.pending.push(msg),
+                Err(TryRecvError::Lagged(_)) => continue,
+                Err(_) => return std::mem::take(&mut self.pending),
+            }
+        }
+    }
+
+    // Checks a message containing the text was published and drops it with every
+    // message before it, so consecutive calls expect messages in order. Messages
+    // of other tests running at the same time are skipped.
+    pub fn expect(&mut self, text: &str) {
+        let mut messages = self.drain();
+        match messages.iter().position(|msg| msg.contains(text)) {
+            Some(index) => self.pending = messages.split_off(index + 1),
+            None => panic!("No event containing {:?}, got {:?}", text, messages),
+        }
+    }
+}
+
+// A fleet playing honestly from a seeded board
+pub struct Player {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    // Declared at join when set
+    pub team: Option<String>,
+    // Placed at join when any, in a game played with mines
+    pub mines: Vec<u8>,
+}
+
+impl Player {
+    pub fn new(gameid: &str, fleet: &str, seed: u64) -> Self {
+        Player {
+            gameid: gameid.to_string(),
+            fleet: fleet.to_string(),
+            board: fleetcore::seeded_board(seed),
+            random: format!("{}-random", fleet),
+            team: None,
+            mines: Vec::new(),
+        }
+    }
+
+    pub fn form(&self, button: &str) -> FormData {
+        FormData {
+            button: button.to_string(),
+            gameid: Some(self.gameid.clone()),
+            fleetid: Some(self.fleet.clone()),
+            targetfleet: None,
+            x: None,
+            y: None,
+            rx: None,
+            ry: None,
+            report: None,
+            board: Some(
+                self.board
+                    .iter()
+                    .map(|pos| pos.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            shots: None,
+            random: Some(self.random.clone()),
+            seed: None,
+            mode: None,
+            salvo: None,
+            team: self.team.clone(),
+            size: None,
+            direction: None,
+            mines: (!self.mines.is_empty()).then(|| salvo(&self.mines)),
+        }
+    }
+
+    pub async fn join(&self) -> String {
+        host::join_game(self.form("Join")).await
+    }
+
+    // Joins, creating the game in the given mode if it does not exist yet
+    pub async fn join_mode(&self, mode: GameMode) -> String {
+        let mut data = self.form("Join");
+        data.mode = Some(format!("{:?}", mode));
+        host::join_game(data).await
+    }
+
+    pub async fn fire(&self, target: &str, pos: u8) -> String {
+        let mut data = self.form("Fire");
+        data.targetfleet = Some(target.to_string());
+        data.x = Some(column(pos));
+        data.y = Some(row(pos));
+        host::fire(data).await
+    }
+
+    pub async fn fire_salvo(&self, target: &str, positions: &[u8]) -> String {
+        let mut data = self.form("Fire");
+        data.targetfleet = Some(target.to_string());
+        data.salvo = Some(salvo(positions));
+        host::fire(data).await
+    }
+
+    // Reports every shot of a salvo at once and keeps the board in step
+    pub async fn report_salvo(&mut self, positions: &[u8]) -> String {
+        let mut data = self.form("ReportSalvo");
+        data.salvo = Some(salvo(positions));
+        let response = host::report_salvo(data).await;
+        if response == "OK" {
+            self.board.retain(|p| !positions.contains(p));
+        }
+        response
+    }
+
+    // Reports the shot truthfully and keeps the board in step with the report guest
+    pub async fn report(&mut self, pos: u8) -> String {
+        let value = if self.board.contains(&pos) {
+            "Hit"
+        } else if self.mines.contains(&pos) {
+            "Mine"
+        } else {
+            "Miss"
+        };
+        let response = self.report_as(pos, value).await;
+        if response == "OK" {
+            self.board.retain(|&p| p != pos);
+        }
+        response
+    }
+
+    // Reports any value, for lying fleets
+    pub async fn report_as(&self, pos: u8, value: &str) -> String {
+        let mut data = self.form("Report");
+        data.report = Some(value.to_string());
+        data.rx = Some(column(pos));
+        data.ry = Some(row(pos));
+        host::report(data).await
+    }
+
+    // Scans a size x size area of the target's board from its top left cell
+    pub async fn scan(&self, target: &str, pos: u8, size: u8) -> String {
+        let mut data = self.form("Scan");
+        data.targetfleet = Some(target.to_string());
+        data.x = Some(column(pos));
+        data.y = Some(row(pos));
+        data.size = Some(size.to_string());
+        host::scan(data).await
+    }
+
+    pub async fn answer_scan(&self, pos: u8, size: u8) -> String {
+        let mut data = self.form("AnswerScan");
+        data.rx = Some(column(pos));
+        data.ry = Some(row(pos));
+        data.size = Some(size.to_string());
+        host::answer_scan(data).await
+    }
+
+    // Moves the ship with a cell at the position and keeps the board in step
+    pub async fn move_ship(&mut self, pos: u8, direction: Direction) -> String {
+        let mut data = self.form("Move");
+        data.x = Some(column(pos));
+        data.y = Some(row(pos));
+        data.direction = Some(format!("{:?}", direction));
+        let moved = host::moved_board(&data);
+        let response = host::move_ship(data).await;
+        if let (Ok(moved), "OK") = (moved, response.as_str()) {
+            self.board = moved;
+        }
+        response
+    }
+
+    pub async fn wave(&self) -> String {
+        host::wave(self.form("Wave")).await
+    }
+
+    // Shows the board and mines the fleet joined with, as the host recorded them
+    pub async fn reveal(&self) -> String {
+        host::reveal(self.form("Reveal")).await
+    }
+
+    pub async fn win(&self) -> String {
+        host::win(self.form("Win")).await
+    }
+
+    // Latest receipt of the command the host proved for the fleet, as it was saved
+    pub fn saved_receipt(&self, cmd: Command) -> CommunicationData {
+        let safe = |id: &str| id.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_");
+        let suffix = format!("-{}-{}-{:?}.json", safe(&self.gameid), safe(&self.fleet), cmd);
+        let name = host::list_receipts().into_iter().rev().find(|name| name.ends_with(&suffix)).expect("No saved receipt");
+        host::load_receipt(&name).unwrap()
+    }
+
+    pub async fn resign(&self) -> String {
+        host::resign(self.form("Resign")).await
+    }
+
+    pub async fn offer_draw(&self) -> String {
+        host::offer_draw(self.form("OfferDraw")).await
+    }
+
+    pub async fn accept_draw(&self) -> String {
+        host::accept_draw(self.form("AcceptDraw")).await
+    }
+}
+
+// Plays a joined two fleet game to its end from the winner's turn: the winner sinks
+// every ship of the loser, who only finds water, then claims victory
+pub async fn play_out(winner: &mut Player, loser: &mut Player) {
+    let targets = loser.board.clone();
+    let water: Vec<u8> = (0..100u8).filter(|pos| !winner.board.contains(pos)).collect();
+    for (&pos, &shot) in targets.iter().zip(&water) {
+        assert_eq!(winner.fire(&loser.fleet, pos).await, "OK");
+        assert_eq!(loser.report(pos).await, "OK");
+        if loser.board.is_empty() {
+            break;
+        }
+        assert_eq!(loser.fire(&winner.fleet, shot).await, "OK");
+        assert_eq!(winner.report(shot).await, "OK");
+    }
+    assert_eq!(winner.win().await, "OK");
+}
+
+// Board positions as the page's coordinates, e.g. 23 is D2
+pub fn position(pos: u8) -> String {
+    format!("{}{}", column(pos), row(pos))
+}
+
+// Positions of a salvo as the page's coordinates, e.g. "D2,E2"
+pub fn salvo(positions: &[u8]) -> String {
+    positions.iter().map(|&pos| position(pos)).collect::<Vec<String>>().join(",")
+}
+
+fn column(pos: u8) -> String {
+    ((b'A' + pos % 10) as char).to_string()
+}
+
+fn row(pos: u8) -> String {
+    (pos / 10).to_string()
+}
@@ -0,0 +1,805 @@
+// End-to-end games between host players and an in-process chain node, in dev mode
+
+use blockchain::{audit, Transcript};
+use e2e::{chain, play_out, position, salvo, Player};
+use fleetcore::{Command, CommunicationData, Direction, GameMode, ScanArea};
+
+#[tokio::test]
+async fn full_game() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut alice = Player::new("e2e-full", "alice", 1);
+    let mut bob = Player::new("e2e-full", "bob", 2);
+
+    assert_eq!(alice.join().await, "OK");
+    events.expect("[fake] Player alice joined game e2e-full");
+    assert_eq!(bob.join().await, "OK");
+    events.expect("[fake] Player bob joined game e2e-full");
+
+    let game = chain.game("e2e-full").await;
+    assert_eq!(game["dev"], true);
+    assert_eq!(game["players"], serde_json::json!(["alice", "bob"]));
+    assert_eq!(game["next_player"], "alice");
+
+    // Alice sinks every ship of bob, while bob only finds water
+    let targets = bob.board.clone();
+    let water: Vec<u8> = (0..100u8).filter(|pos| !alice.board.contains(pos)).collect();
+    let mut misses = water.into_iter();
+    for (turn, &pos) in targets.iter().enumerate() {
+        assert_eq!(alice.fire("bob", pos).await, "OK");
+        events.expect(&format!("Player alice fired at player bob at pos {}", position(pos)));
+        let game = chain.game("e2e-full").await;
+        assert_eq!(game["next_player"], "bob");
+        assert_eq!(game["pending_reports"], serde_json::json!([position(pos)]));
+
+        assert_eq!(bob.report(pos).await, "OK");
+        events.expect(&format!("Player bob reported Hit at pos {}", position(pos)));
+
+        if turn + 1 == targets.len() {
+            break;
+        }
+        events.expect("Player bob to fire in game e2e-full");
+        let shot = misses.next().unwrap();
+        assert_eq!(bob.fire("alice", shot).await, "OK");
+        events.expect(&format!("Player bob fired at player alice at pos {}", position(shot)));
+        assert_eq!(alice.report(shot).await, "OK");
+        events.expect(&format!("Player alice reported Miss at pos {}", position(shot)));
+        events.expect("Player alice to fire in game e2e-full");
+    }
+    assert!(bob.board.is_empty());
+    events.expect("Player bob eliminated from game e2e-full");
+    events.expect("Player alice is the last fleet standing in game e2e-full");
+
+    assert_eq!(alice.win().await, "OK");
+    events.expect("[fake] Player alice claimed victory");
+    let game = chain.game("e2e-full").await;
+    assert_eq!(game["winner"], "alice");
+    assert_eq!(game["eliminated"], serde_json::json!(["bob"]));
+    assert!(game["pending_reports"] == serde_json::json!([]));
+
+    // Nothing happens after victory
+    assert_eq!(bob.wave().await, "Game e2e-full is over, player alice won");
+
+    // Spectators get the game from its start, or from after the last event they saw
+    let events = chain.game_events("e2e-full", None, 3).await;
+    assert_eq!(events[0], (0, "[fake] Player alice joined game e2e-full".to_string()));
+    assert_eq!(events[1], (1, "[fake] Player bob joined game e2e-full".to_string()));
+    assert!(events[2].1.starts_with("[fake] Player alice fired at player bob"));
+    let resumed = chain.game_events("e2e-full", Some(1), 1).await;
+    assert_eq!(resumed, events[2..]);
+
+    // The win is rated, evenly matched fleets move by half the K factor
+    let rating = chain.player("alice").await;
+    assert_eq!(rating["rating"], 1516.0);
+    assert_eq!(rating["history"][0]["gameid"], "e2e-full");
+    assert_eq!(rating["history"][0]["opponents"], serde_json::json!(["bob"]));
+    assert_eq!(chain.player("bob").await["rating"], 1484.0);
+    let leaderboard = chain.leaderboard().await;
+    let rank = |fleet: &str| leaderboard.as_array().unwrap().iter().position(|s| s["fleet"] == fleet);
+    assert!(rank("alice").unwrap() < rank("bob").unwrap());
+    assert!(chain.player("carol").await.is_null());
+
+    // A dev game won with fake receipts stays off the real leaderboard
+    let real = chain.real_leaderboard().await;
+    assert!(!real.as_array().unwrap().iter().any(|s| s["fleet"] == "alice"));
+
+    // A node restarted from the transaction log replays the game and its rating
+    let restarted = chain.restarted();
+    assert_eq!(restarted.game("e2e-full").await, chain.game("e2e-full").await);
+    assert_eq!(restarted.player("alice").await, rating);
+}
+
+#[tokio::test]
+async fn corrupt_chain_log() {
+    let chain = chain();
+    let sara = Player::new("e2e-corrupt-log", "sara", 48);
+    assert_eq!(sara.join().await, "OK");
+
+    // A line that is no transaction and one cut short by a crash are left out
+    let restarted = chain.restarted_with("not a transaction\n{\"time\":1,\"cmd\":");
+    assert_eq!(restarted.game("e2e-corrupt-log").await, chain.game("e2e-corrupt-log").await);
+}
+
+#[tokio::test]
+async fn illegal_moves() {
+    let chain = chain();
+    let mut events = chain.events();
+    let carol = Player::new("e2e-illegal", "carol", 3);
+    let mut dave = Player::new("e2e-illegal", "dave", 4);
+
+    // An invalid fleet is refused by the host before anything reaches the chain
+    let mut cheater = Player::new("e2e-illegal", "eve", 5);
+    cheater.board.pop();
+    assert!(cheater.join().await.starts_with("Invalid move:"));
+    assert!(chain.game("e2e-illegal").await.is_null());
+
+    assert_eq!(carol.join().await, "OK");
+    assert_eq!(dave.join().await, "OK");
+    events.drain();
+    assert_eq!(carol.join().await, "Player carol already in game e2e-illegal");
+    events.expect("Player carol already in game e2e-illegal");
+
+    assert_eq!(dave.fire("carol", 0).await, "Player dave, not your turn dummy!");
+    events.expect("Player dave, not your turn dummy!");
+    assert_eq!(carol.fire("nobody", 0).await, "Player nobody not in game");
+    events.expect("Player nobody not in game");
+    assert_eq!(dave.report(0).await, "No report to handle in this game");
+    events.expect("No report to handle in this game");
+
+    let pos = dave.board[0];
+    assert_eq!(carol.fire("dave", pos).await, "OK");
+    events.expect(&format!("Player carol fired at player dave at pos {}", position(pos)));
+    assert_eq!(carol.fire("dave", pos).await, "Player carol, not your turn dummy!");
+    events.expect("Player carol, not your turn dummy!");
+
+    // A lying report can't be proven, so the chain never sees it
+    let response = dave.report_as(pos, "Miss").await;
+    assert!(response.starts_with("Invalid move: Reported Miss"), "{}", response);
+    assert!(events.drain().iter().all(|msg| !msg.contains("Player dave reported")));
+    assert_eq!(chain.game("e2e-illegal").await["pending_reports"], serde_json::json!([position(pos)]));
+
+    // The rejected report above does not break the history of the next one
+    assert_eq!(dave.report(pos).await, "OK");
+    events.expect(&format!("[fake] Player dave reported Hit at pos {}", position(pos)));
+
+    // Victory claimed with the board dave joined with, not the one left after the hit
+    let stale = Player::new("e2e-illegal", "dave", 4);
+    assert_eq!(stale.win().await, "Player dave claiming victory with the wrong board!!!");
+    events.expect("Player dave claiming victory with the wrong board!!!");
+
+    let mut water = (0..100u8).filter(|pos| !carol.board.contains(pos));
+    let (shot, elsewhere) = (water.next().unwrap(), water.next().unwrap());
+    assert_eq!(dave.fire("carol", shot).await, "OK");
+    let wrong = format!("Player carol reported wrong position. Shot was at pos {}", position(shot));
+    assert_eq!(carol.report_as(elsewhere, "Miss").await, wrong);
+    events.expect(&wrong);
+
+    // Refused moves only go to the node's log, so the game's events and their ids are
+    // the same on a node restarted from the transactions it accepted
+    let spectated = chain.game_events("e2e-illegal", None, 5).await;
+    assert!(spectated.iter().all(|(_, msg)| !msg.contains("not your turn") && !msg.contains("already in game")));
+    assert!(spectated[2].1.ends_with(&format!("Player carol fired at player dave at pos {}", position(pos))));
+    assert_eq!(chain.restarted().game_events("e2e-illegal", None, 5).await, spectated);
+}
+
+#[tokio::test]
+async fn three_players() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut players = vec![
+        Player::new("e2e-three", "frank", 6),
+        Player::new("e2e-three", "grace", 7),
+        Player::new("e2e-three", "heidi", 8),
+    ];
+    for player in &players {
+        assert_eq!(player.join().await, "OK");
+        events.expect(&format!("[fake] Player {} joined game e2e-three", player.fleet));
+    }
+    let game = chain.game("e2e-three").await;
+    assert_eq!(game["players"], serde_json::json!(["frank", "grace", "heidi"]));
+    assert_eq!(game["next_player"], "frank");
+
+    // Frank fires at heidi, the turn then goes on to grace, not to heidi
+    let pos = players[2].board[0];
+    assert_eq!(players[0].fire("heidi", pos).await, "OK");
+    events.expect(&format!("Player frank fired at player heidi at pos {}", position(pos)));
+    assert_eq!(players[1].wave().await, "Must address report first");
+    assert_eq!(players[2].report(pos).await, "OK");
+    events.expect(&format!("Player heidi reported Hit at pos {}", position(pos)));
+    events.expect("Player grace to fire in game e2e-three");
+    assert_eq!(chain.game("e2e-three").await["next_player"], "grace");
+    assert_eq!(players[2].wave().await, "Player heidi, not your turn dummy!");
+
+    assert_eq!(players[1].fire("grace", 0).await, "Player grace can't fire at their own fleet");
+    assert_eq!(players[1].wave().await, "OK");
+    events.expect("[fake] Player grace waves their turn");
+    events.expect("Player heidi to fire in game e2e-three");
+
+    // Joining closes with the first shot, and victory needs every opponent sunk
+    let late = Player::new("e2e-three", "ivan", 9);
+    assert_eq!(late.join().await, "Game e2e-three already started");
+    assert_eq!(
+        players[0].win().await,
+        "Player frank can't claim victory, still afloat: grace, heidi"
+    );
+    let game = chain.game("e2e-three").await;
+    assert_eq!(game["eliminated"], serde_json::json!([]));
+    assert!(game["winner"].is_null());
+}
+
+#[tokio::test]
+async fn salvo_game() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut judy = Player::new("e2e-salvo", "judy", 10);
+    let mut kim = Player::new("e2e-salvo", "kim", 11);
+
+    assert_eq!(judy.join_mode(GameMode::Salvo).await, "OK");
+    assert_eq!(kim.join_mode(GameMode::Classic).await, "Game e2e-salvo is played in Salvo mode");
+    assert_eq!(kim.join().await, "OK");
+    assert_eq!(chain.game("e2e-salvo").await["mode"], "Salvo");
+
+    // Seven ships afloat, so no more than seven shots
+    let water: Vec<u8> = (0..100u8).filter(|pos| !kim.board.contains(pos)).collect();
+    let too_many = [&kim.board[..4], &water[..4]].concat();
+    assert!(judy.fire_salvo("kim", &too_many).await.starts_with("Invalid move: Fired 8 shots"));
+
+    let shots = [&kim.board[..3], &water[..4]].concat();
+    assert_eq!(judy.fire_salvo("kim", &shots).await, "OK");
+    events.expect(&format!("Player judy fired at player kim at pos {}", salvo(&shots)));
+    assert_eq!(kim.report(shots[0]).await, "Game e2e-salvo is played in Salvo mode, report every shot at once");
+    assert_eq!(
+        kim.report_salvo(&shots[..2]).await,
+        format!("Player kim reported wrong positions. Shots were at pos {}", salvo(&shots))
+    );
+
+    // The rejected batch above does not break the history of the next one
+    assert_eq!(kim.report_salvo(&shots).await, "OK");
+    events.expect(&format!("Player kim reported Hit,Hit,Hit,Miss,Miss,Miss,Miss at pos {}", salvo(&shots)));
+    events.expect("Player kim to fire in game e2e-salvo");
+    assert_eq!(chain.game("e2e-salvo").await["pending_reports"], serde_json::json!([]));
+
+    // A single shot is a salvo of one
+    let target = judy.board[0];
+    assert_eq!(kim.fire("judy", target).await, "OK");
+    assert_eq!(judy.report_salvo(&[target]).await, "OK");
+    events.expect(&format!("Player judy reported Hit at pos {}", position(target)));
+}
+
+#[tokio::test]
+async fn radar_scan() {
+    let chain = chain();
+    let mut events = chain.events();
+    let tom = Player::new("e2e-scan", "tom", 20);
+    let uma = Player::new("e2e-scan", "uma", 21);
+    assert_eq!(tom.join().await, "OK");
+    assert_eq!(uma.join().await, "OK");
+
+    // The scanned fleet answers before anyone moves on
+    assert_eq!(tom.scan("uma", 0, 3).await, "OK");
+    events.expect("[fake] Player tom scanned player uma at A0:C2");
+    assert_eq!(chain.game("e2e-scan").await["pending_scan"], "A0:C2");
+    assert_eq!(uma.fire("tom", 0).await, "Must address report first");
+    assert_eq!(uma.answer_scan(11, 3).await, "Player uma answered for the wrong area. Scan was at A0:C2");
+
+    // Only the number of ship cells in the area is revealed
+    let area = ScanArea { pos: 0, size: 3 };
+    let count = uma.board.iter().filter(|&&pos| area.contains(pos)).count();
+    assert_eq!(uma.answer_scan(0, 3).await, "OK");
+    events.expect(&format!("[fake] Player uma has {} ship cells at A0:C2", count));
+    events.expect("Player uma to fire in game e2e-scan");
+    assert!(chain.game("e2e-scan").await["pending_scan"].is_null());
+    assert_eq!(uma.answer_scan(0, 3).await, "No scan to answer in this game");
+
+    // The node sets the size of a scan and how many each fleet gets in a game
+    assert_eq!(uma.wave().await, "OK");
+    assert_eq!(tom.scan("uma", 0, 4).await, "Scans in game e2e-scan cover 3x3 cells on the board");
+    assert_eq!(tom.scan("uma", 77, 3).await, "OK");
+    events.expect("Player tom scanned player uma at H7:J9");
+    assert_eq!(uma.answer_scan(77, 3).await, "OK");
+    assert_eq!(uma.wave().await, "OK");
+    assert_eq!(tom.scan("uma", 33, 3).await, "Player tom has no scans left in game e2e-scan");
+}
+
+#[tokio::test]
+async fn movable_ships() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut vera = Player::new("e2e-move", "vera", 22);
+    let mut walt = Player::new("e2e-move", "walt", 23);
+    assert_eq!(vera.join_mode(GameMode::Movable).await, "OK");
+    assert_eq!(walt.join().await, "OK");
+
+    // A move takes the turn, and nobody learns which ship moved where
+    let directions = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+    let (ship, direction) = vera
+        .board
+        .iter()
+        .flat_map(|&pos| directions.map(|direction| (pos, direction)))
+        .find(|&(pos, direction)| fleetcore::move_ship(&vera.board, &[], &[], pos, direction).is_ok())
+        .unwrap();
+    assert_eq!(walt.move_ship(walt.board[0], Direction::Up).await, "Player walt, not your turn dummy!");
+    assert_eq!(vera.move_ship(ship, direction).await, "OK");
+    events.expect("[fake] Player vera moved a ship");
+    events.expect("Player walt to fire in game e2e-move");
+
+    // The report after the move carries the fleet's history on from the moved board
+    let hit = *vera.board.iter().find(|&&pos| vera.board.contains(&(pos + 1))).unwrap();
+    assert_eq!(walt.fire("vera", hit).await, "OK");
+    assert_eq!(vera.report(hit).await, "OK");
+    events.expect(&format!("Player vera reported Hit at pos {}", position(hit)));
+
+    // A ship that was hit stays where it is
+    assert_eq!(
+        vera.move_ship(hit + 1, Direction::Down).await,
+        format!("Invalid move: The ship at position {} was hit and can't move", hit + 1)
+    );
+    assert_eq!(vera.wave().await, "OK");
+
+    // Ships only move in a Movable game
+    let mut xena = Player::new("e2e-move-classic", "xena", 24);
+    assert_eq!(xena.join().await, "OK");
+    let (ship, direction) = xena
+        .board
+        .iter()
+        .flat_map(|&pos| directions.map(|direction| (pos, direction)))
+        .find(|&(pos, direction)| fleetcore::move_ship(&xena.board, &[], &[], pos, direction).is_ok())
+        .unwrap();
+    assert_eq!(
+        xena.move_ship(ship, direction).await,
+        "Ships can't move in game e2e-move-classic, it is played in Classic mode"
+    );
+}
+
+#[tokio::test]
+async fn mines() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut players = Vec::new();
+    for (fleet, seed) in [("yara", 25), ("zack", 26)] {
+        let mut player = Player::new("e2e-mines", fleet, seed);
+        player.mines = (0..100u8).filter(|pos| !player.board.contains(pos)).take(3).collect();
+        players.push(player);
+    }
+    let (mut yara, mut zack) = (players.remove(0), players.remove(0));
+
+    // Mines go in the water, and every fleet of the game places them
+    let mut careless = Player::new("e2e-mines-ship", "abel", 27);
+    careless.mines = vec![careless.board[0], 98, 99];
+    assert_eq!(
+        careless.join().await,
+        format!("Invalid move: Mine at position {} is on a ship", careless.board[0])
+    );
+    assert_eq!(yara.join().await, "OK");
+    let unmined = Player::new("e2e-mines", "abel", 27);
+    assert_eq!(unmined.join().await, "Game e2e-mines is played with mines, place 3 to join");
+    assert_eq!(zack.join().await, "OK");
+    assert_eq!(chain.game("e2e-mines").await["mines"], true);
+
+    // Firing on a mine costs the shooter its next turn
+    let mine = zack.mines[0];
+    assert_eq!(yara.fire("zack", mine).await, "OK");
+    assert_eq!(zack.report(mine).await, "OK");
+    events.expect(&format!("[fake] Player zack reported Mine at pos {}", position(mine)));
+    events.expect("Player yara fired on a mine and loses their next turn");
+    events.expect("Player zack to fire in game e2e-mines");
+    assert_eq!(zack.wave().await, "OK");
+    events.expect("Player zack to fire in game e2e-mines");
+
+    // The report guest proves the mine, it can't be hidden
+    let mine = yara.mines[0];
+    assert_eq!(zack.fire("yara", mine).await, "OK");
+    assert_eq!(
+        yara.report_as(mine, "Miss").await,
+        format!("Invalid move: Reported Miss but the shot at position {} is a Mine", mine)
+    );
+    assert_eq!(yara.report(mine).await, "OK");
+    events.expect("Player zack fired on a mine and loses their next turn");
+    assert_eq!(chain.game("e2e-mines").await["next_player"], "yara");
+}
+
+#[tokio::test]
+async fn knockout_tournament() {
+    let chain = chain();
+    let mut events = chain.events();
+    let create = serde_json::json!({"action": "Create", "tournament": "e2e-cup", "format": "SingleElimination", "dev": true});
+    assert_eq!(chain.tournament_action(create.clone()).await, "OK");
+    assert_eq!(chain.tournament_action(create).await, "Tournament e2e-cup already exists");
+    for fleet in ["lena", "mike", "nora"] {
+        let register = serde_json::json!({"action": "Register", "tournament": "e2e-cup", "fleet": fleet});
+        assert_eq!(chain.tournament_action(register).await, "OK");
+    }
+    let start = serde_json::json!({"action": "Start", "tournament": "e2e-cup"});
+    assert_eq!(chain.tournament_action(start.clone()).await, "OK");
+    assert_eq!(chain.tournament_action(start).await, "Tournament e2e-cup already started");
+    events.expect("Tournament e2e-cup round 1: lena vs mike play game e2e-cup-r1-m1");
+    events.expect("Tournament e2e-cup round 1: player nora goes through on a bye");
+
+    // Matches are joined and played like any game, by the drawn fleets only
+    let mut lena = Player::new("e2e-cup-r1-m1", "lena", 12);
+    let mut mike = Player::new("e2e-cup-r1-m1", "mike", 13);
+    let mut nora = Player::new("e2e-cup-r1-m1", "nora", 14);
+    assert_eq!(nora.join().await, "Player nora is not drawn to play game e2e-cup-r1-m1");
+    assert_eq!(lena.join().await, "OK");
+    assert_eq!(mike.join().await, "OK");
+    assert_eq!(chain.game("e2e-cup-r1-m1").await["tournament"], "e2e-cup");
+    play_out(&mut lena, &mut mike).await;
+    events.expect("Tournament e2e-cup round 2: lena vs nora play game e2e-cup-r2-m1");
+
+    // A match is audited with the seats it was drawn with
+    let exported = chain.transcript("e2e-cup-r1-m1", true).await;
+    assert_eq!(exported["tournament"], "e2e-cup");
+    let transcript: Transcript = serde_json::from_value(exported.clone()).unwrap();
+    assert!(audit(&transcript).unwrap().contains("moves in tournament e2e-cup, won by lena"));
+    let mut reseated = exported;
+    reseated["seats"] = serde_json::json!(["lena", "nora"]);
+    let reseated: Transcript = serde_json::from_value(reseated).unwrap();
+    let refused = audit(&reseated).unwrap_err();
+    assert!(refused.starts_with("Move 2 (Join by mike): rejected: Player mike is not drawn"), "{}", refused);
+
+    // Nobody can take the id of a game the tournament has yet to draw
+    let squatter = Player::new("e2e-cup-r3-m1", "mike", 13);
+    assert_eq!(squatter.join().await, "Game e2e-cup-r3-m1 is reserved for tournament e2e-cup");
+
+    let mut lena = Player::new("e2e-cup-r2-m1", "lena", 12);
+    nora.gameid = "e2e-cup-r2-m1".to_string();
+    assert_eq!(nora.join().await, "OK");
+    assert_eq!(lena.join().await, "OK");
+    play_out(&mut nora, &mut lena).await;
+    events.expect("Tournament e2e-cup is over, player nora won");
+
+    let cup = chain.tournament("e2e-cup").await;
+    assert_eq!(cup["champion"], "nora");
+    assert_eq!(cup["matches"].as_array().unwrap().len(), 3);
+    let standings = chain.standings("e2e-cup").await;
+    let fleets: Vec<&str> = standings.as_array().unwrap().iter().map(|s| s["fleet"].as_str().unwrap()).collect();
+    assert_eq!(fleets, ["nora", "lena", "mike"]);
+    assert_eq!(standings[1]["out"], true);
+}
+
+#[tokio::test]
+async fn real_tournament_refuses_fake_receipts() {
+    let chain = chain();
+    let create = serde_json::json!({"action": "Create", "tournament": "e2e-real-cup", "format": "RoundRobin"});
+    assert_eq!(chain.tournament_action(create).await, "OK");
+    for fleet in ["rhea", "saul"] {
+        let register = serde_json::json!({"action": "Register", "tournament": "e2e-real-cup", "fleet": fleet});
+        assert_eq!(chain.tournament_action(register).await, "OK");
+    }
+    let start = serde_json::json!({"action": "Start", "tournament": "e2e-real-cup"});
+    assert_eq!(chain.tournament_action(start).await, "OK");
+
+    // The match can't be turned into a dev game by joining it first
+    let rhea = Player::new("e2e-real-cup-r1-m1", "rhea", 43);
+    assert_eq!(rhea.join().await, "Could not verify receipt");
+    assert!(chain.game("e2e-real-cup-r1-m1").await["players"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn team_game() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut players: Vec<Player> = [("olga", "red", 15), ("pete", "red", 16), ("quinn", "blue", 17), ("rita", "blue", 18)]
+        .into_iter()
+        .map(|(fleet, team, seed)| Player { team: Some(team.to_string()), ..Player::new("e2e-teams", fleet, seed) })
+        .collect();
+    for player in &players {
+        assert_eq!(player.join().await, "OK");
+        events.expect(&format!("Player {} joined game e2e-teams for team {}", player.fleet, player.team.as_ref().unwrap()));
+    }
+    let loner = Player::new("e2e-teams", "sam", 19);
+    assert_eq!(loner.join().await, "Game e2e-teams is played in teams, declare one to join");
+    let game = chain.game("e2e-teams").await;
+    assert_eq!(game["teams"], serde_json::json!({"blue": ["quinn", "rita"], "red": ["olga", "pete"]}));
+
+    // Turns alternate between the teams, each team's members taking theirs in join order
+    let pos = players[1].board[0];
+    assert_eq!(players[0].fire("pete", pos).await, "Player olga can't fire at teammate pete");
+    let water = (0..100u8).find(|pos| !players[2].board.contains(pos)).unwrap();
+    assert_eq!(players[0].fire("quinn", water).await, "OK");
+    assert_eq!(players[2].report(water).await, "OK");
+    for next in ["quinn", "pete", "rita", "olga"] {
+        events.expect(&format!("Player {} to fire in game e2e-teams", next));
+        if next != "olga" {
+            let index = players.iter().position(|p| p.fleet == next).unwrap();
+            assert_eq!(players[index].wave().await, "OK");
+        }
+    }
+
+    // Red sinks both blue fleets while blue only waves, then either red fleet wins
+    loop {
+        let game = chain.game("e2e-teams").await;
+        let Some(next) = game["next_player"].as_str() else { break };
+        let shooter = players.iter().position(|p| p.fleet == next).unwrap();
+        if players[shooter].team.as_deref() == Some("blue") {
+            assert_eq!(players[shooter].wave().await, "OK");
+            continue;
+        }
+        let Some(target) = players.iter().position(|p| p.team.as_deref() == Some("blue") && !p.board.is_empty()) else {
+            break;
+        };
+        let (fleet, pos) = (players[target].fleet.clone(), players[target].board[0]);
+        assert_eq!(players[shooter].fire(&fleet, pos).await, "OK");
+        assert_eq!(players[target].report(pos).await, "OK");
+    }
+    events.expect("Player quinn eliminated from game e2e-teams");
+    events.expect("Player rita eliminated from game e2e-teams");
+    events.expect("Team red is the last team standing in game e2e-teams");
+    assert_eq!(players[1].win().await, "OK");
+    events.expect("[fake] Player pete claimed victory for team red");
+    assert_eq!(players[0].wave().await, "Game e2e-teams is over, team red won");
+
+    // Every winner is rated against every loser
+    assert_eq!(chain.player("olga").await["rating"], 1516.0);
+    assert_eq!(chain.player("rita").await["rating"], 1484.0);
+
+    // The audit rebuilds the teams from the joins and checks them against the transcript
+    let exported = chain.transcript("e2e-teams", true).await;
+    assert_eq!(exported["teams"], game["teams"]);
+    let transcript: Transcript = serde_json::from_value(exported.clone()).unwrap();
+    assert!(audit(&transcript).unwrap().contains("moves, won by pete"));
+    let mut regrouped = exported;
+    regrouped["teams"] = serde_json::json!({"blue": ["quinn"], "red": ["olga", "pete", "rita"]});
+    let regrouped: Transcript = serde_json::from_value(regrouped).unwrap();
+    assert!(audit(&regrouped).unwrap_err().starts_with("Game e2e-teams was played by teams"));
+}
+
+#[tokio::test]
+async fn game_transcript() {
+    let chain = chain();
+    let mut bert = Player::new("e2e-transcript", "bert", 28);
+    let mut cleo = Player::new("e2e-transcript", "cleo", 29);
+    assert_eq!(bert.join().await, "OK");
+    assert_eq!(cleo.join().await, "OK");
+    play_out(&mut bert, &mut cleo).await;
+
+    // Every accepted move in order, with the journal and what the node published for it
+    let transcript = chain.transcript("e2e-transcript", false).await;
+    assert_eq!(transcript["winner"], "bert");
+    assert_eq!(transcript["mode"], "Classic");
+    let moves = transcript["moves"].as_array().unwrap();
+    assert_eq!(moves[0]["cmd"], "Join");
+    assert_eq!(moves[0]["fleet"], "bert");
+    assert_eq!(moves[0]["events"], serde_json::json!(["[fake] Player bert joined game e2e-transcript"]));
+    assert_eq!(moves[2]["cmd"], "Fire");
+    assert_eq!(moves[2]["journal"]["target"], "cleo");
+    assert_eq!(moves[3]["cmd"], "Report");
+    assert_eq!(moves[2]["journal"]["positions"], serde_json::json!([moves[3]["journal"]["pos"]]));
+    assert_eq!(moves.last().unwrap()["cmd"], "Win");
+    assert!(moves.windows(2).all(|pair| pair[0]["index"].as_u64() < pair[1]["index"].as_u64()));
+    assert!(moves.iter().all(|played| played["time"].as_u64().unwrap() > 0 && played.get("receipt").is_none()));
+
+    // Receipts only when asked for
+    let audited = chain.transcript("e2e-transcript", true).await;
+    assert!(audited["moves"].as_array().unwrap().iter().all(|played| played["receipt"].is_object()));
+    assert!(chain.transcript("e2e-no-such-game", false).await.is_null());
+
+    // A restarted node keeps the times and publishes the same events again
+    assert_eq!(chain.restarted().transcript("e2e-transcript", false).await, transcript);
+}
+
+#[tokio::test]
+async fn transcript_audit() {
+    let chain = chain();
+    let mut dora = Player::new("e2e-audit", "dora", 30);
+    let mut emil = Player::new("e2e-audit", "emil", 31);
+    assert_eq!(dora.join().await, "OK");
+    assert_eq!(emil.join().await, "OK");
+    play_out(&mut dora, &mut emil).await;
+
+    let exported = chain.transcript("e2e-audit", true).await;
+    let transcript: Transcript = serde_json::from_value(exported.clone()).unwrap();
+    let summary = audit(&transcript).unwrap();
+    assert!(summary.starts_with(&format!("Game e2e-audit checks out: {} moves, won by dora", transcript.moves.len())));
+
+    // The first move that does not add up is reported
+    let mut doctored = exported.clone();
+    doctored["moves"][3]["events"][0] = "Player emil reported Miss at pos A0".into();
+    let doctored: Transcript = serde_json::from_value(doctored).unwrap();
+    assert!(audit(&doctored).unwrap_err().starts_with("Move 4 (Report by emil): published"));
+
+    let mut reordered = exported.clone();
+    reordered["moves"].as_array_mut().unwrap().swap(2, 3);
+    let reordered: Transcript = serde_json::from_value(reordered).unwrap();
+    assert!(audit(&reordered).unwrap_err().starts_with("Move 3 (Report by emil): rejected"));
+
+    let mut unproven = exported;
+    unproven["moves"][1].as_object_mut().unwrap().remove("receipt");
+    let unproven: Transcript = serde_json::from_value(unproven).unwrap();
+    assert!(audit(&unproven).unwrap_err().starts_with("Move 2 (Join by emil): no receipt"));
+}
+
+#[tokio::test]
+async fn fleet_reveal() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mut fynn = Player::new("e2e-reveal", "fynn", 32);
+    let mut gwen = Player::new("e2e-reveal", "gwen", 33);
+    let joined = gwen.board.clone();
+    assert_eq!(fynn.join().await, "OK");
+    assert_eq!(gwen.join().await, "OK");
+
+    // Boards stay hidden while the game is played
+    assert_eq!(gwen.reveal().await, "Game e2e-reveal is not over, fleets are revealed once it is");
+    play_out(&mut fynn, &mut gwen).await;
+
+    // The nonce must open the commitment made at join
+    let mut forger = Player::new("e2e-reveal", "gwen", 33);
+    forger.random = "guessed".to_string();
+    assert_eq!(forger.reveal().await, "Player gwen revealed a board that does not match their join");
+
+    // Every shot fynn fired was on a ship, and gwen reported each of them
+    assert_eq!(gwen.reveal().await, "OK");
+    events.expect(&format!("Player gwen revealed their fleet in game e2e-reveal, {} reports check out", joined.len()));
+    assert_eq!(gwen.reveal().await, "Player gwen already revealed their fleet");
+
+    let revealed = &chain.game("e2e-reveal").await["revealed"];
+    let board: Vec<String> = joined.iter().map(|&pos| position(pos)).collect();
+    assert_eq!(revealed["gwen"]["board"], serde_json::json!(board));
+    assert_eq!(revealed["gwen"]["misreports"], serde_json::json!([]));
+    assert!(revealed["fynn"].is_null());
+}
+
+#[tokio::test]
+async fn repeated_shots() {
+    let chain = chain();
+    let mut hana = Player::new("e2e-repeat", "hana", 34);
+    let mut ivan = Player::new("e2e-repeat", "ivan", 35);
+    assert_eq!(hana.join().await, "OK");
+    assert_eq!(ivan.join().await, "OK");
+
+    let miss = (0..100u8).find(|pos| !ivan.board.contains(pos)).unwrap();
+    let reply = (0..100u8).find(|pos| !hana.board.contains(pos)).unwrap();
+    assert_eq!(hana.fire("ivan", miss).await, "OK");
+    assert_eq!(ivan.report(miss).await, "OK");
+    assert_eq!(ivan.fire("hana", reply).await, "OK");
+    assert_eq!(hana.report(reply).await, "OK");
+
+    // A cell is fired at once, the same cell of another fleet is still fair game
+    assert_eq!(
+        hana.fire("ivan", miss).await,
+        format!("Player hana already fired at player ivan at pos {}", position(miss))
+    );
+    let hit = ivan.board[0];
+    assert_eq!(hana.fire("ivan", hit).await, "OK");
+
+    // Every shot with its result once reported
+    let shots = &chain.game("e2e-repeat").await["shots"];
+    assert_eq!(
+        *shots,
+        serde_json::json!([
+            {"shooter": "hana", "target": "ivan", "pos": position(miss), "result": "Miss"},
+            {"shooter": "ivan", "target": "hana", "pos": position(reply), "result": "Miss"},
+            {"shooter": "hana", "target": "ivan", "pos": position(hit), "result": null},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn resignation() {
+    let chain = chain();
+    let mut events = chain.events();
+    let jack = Player::new("e2e-resign", "jack", 36);
+    let kira = Player::new("e2e-resign", "kira", 37);
+    let liam = Player::new("e2e-resign", "liam", 38);
+    for player in [&jack, &kira, &liam] {
+        assert_eq!(player.join().await, "OK");
+    }
+
+    // Liam resigns with jack's shot unanswered, the turn passes on from jack
+    assert_eq!(jack.fire("liam", liam.board[0]).await, "OK");
+    assert_eq!(liam.resign().await, "OK");
+    events.expect("[fake] Player liam resigned from game e2e-resign");
+    events.expect("Player liam eliminated from game e2e-resign");
+    events.expect("Player kira to fire in game e2e-resign");
+    assert_eq!(liam.resign().await, "Player liam is eliminated");
+    let game = chain.game("e2e-resign").await;
+    assert_eq!(game["eliminated"], serde_json::json!(["liam"]));
+    assert_eq!(game["pending_reports"], serde_json::json!([]));
+    assert_eq!(game["next_player"], "kira");
+
+    // Kira resigns on her turn and leaves jack the winner, with no victory left to claim
+    assert_eq!(kira.resign().await, "OK");
+    events.expect("[fake] Player kira resigned from game e2e-resign");
+    events.expect("Game e2e-resign is over, player jack won");
+    assert_eq!(jack.win().await, "Game e2e-resign is over, player jack won");
+    let game = chain.game("e2e-resign").await;
+    assert_eq!(game["winner"], "jack");
+    assert!(game["next_player"].is_null());
+
+    // The winner is rated as for any victory
+    let rating = chain.player("jack").await;
+    assert_eq!(rating["wins"], 1);
+    assert!(rating["rating"].as_f64().unwrap() > 1500.0);
+    assert!(chain.player("liam").await["rating"].as_f64().unwrap() < 1500.0);
+}
+
+#[tokio::test]
+async fn draw_offer() {
+    let chain = chain();
+    let mut events = chain.events();
+    let mona = Player::new("e2e-draw", "mona", 39);
+    let mut nils = Player::new("e2e-draw", "nils", 40);
+    assert_eq!(mona.join().await, "OK");
+    assert_eq!(nils.join().await, "OK");
+    assert_eq!(nils.accept_draw().await, "No draw offered in game e2e-draw");
+
+    // A shot withdraws the offer
+    assert_eq!(mona.offer_draw().await, "OK");
+    events.expect("[fake] Player mona offered a draw in game e2e-draw");
+    assert_eq!(chain.game("e2e-draw").await["draw_offer"], serde_json::json!(["mona"]));
+    assert_eq!(mona.offer_draw().await, "Player mona already offered a draw in game e2e-draw");
+    assert_eq!(mona.accept_draw().await, "Player mona already agreed to the draw in game e2e-draw");
+    let miss = (0..100u8).find(|pos| !nils.board.contains(pos)).unwrap();
+    assert_eq!(mona.fire("nils", miss).await, "OK");
+    events.expect("The draw offered in game e2e-draw is withdrawn");
+    assert_eq!(nils.accept_draw().await, "No draw offered in game e2e-draw");
+    assert_eq!(nils.report(miss).await, "OK");
+
+    // The game ends once every fleet afloat agreed
+    assert_eq!(nils.offer_draw().await, "OK");
+    assert_eq!(mona.accept_draw().await, "OK");
+    events.expect("[fake] Player mona accepted the draw in game e2e-draw");
+    events.expect("Game e2e-draw ended in a draw");
+    assert_eq!(nils.fire("mona", 0).await, "Game e2e-draw is over, it ended in a draw");
+    assert_eq!(mona.win().await, "Game e2e-draw is over, it ended in a draw");
+    let game = chain.game("e2e-draw").await;
+    assert_eq!(game["drawn"], true);
+    assert!(game["winner"].is_null());
+    assert!(game["next_player"].is_null());
+    assert_eq!(chain.transcript("e2e-draw", false).await["drawn"], true);
+    assert_eq!(mona.reveal().await, "OK");
+
+    // Fleets rated the same draw without moving
+    for fleet in ["mona", "nils"] {
+        let rating = chain.player(fleet).await;
+        assert_eq!(rating["games"], 1);
+        assert_eq!(rating["wins"], 0);
+        assert_eq!(rating["draws"], 1);
+        assert_eq!(rating["rating"], 1500.0);
+    }
+}
+
+#[tokio::test]
+async fn replayed_draw_receipts() {
+    let chain = chain();
+    let mut events = chain.events();
+    let owen = Player::new("e2e-draw-replay", "owen", 44);
+    let pia = Player::new("e2e-draw-replay", "pia", 45);
+    assert_eq!(owen.join().await, "OK");
+    assert_eq!(pia.join().await, "OK");
+
+    // An offer withdrawn by a shot can't be made again with its old receipt
+    assert_eq!(owen.offer_draw().await, "OK");
+    let offer = owen.saved_receipt(Command::OfferDraw);
+    let miss = (0..100u8).find(|pos| !pia.board.contains(pos)).unwrap();
+    assert_eq!(owen.fire("pia", miss).await, "OK");
+    events.expect("The draw offered in game e2e-draw-replay is withdrawn");
+    assert_eq!(
+        host::submit_data(&offer).await,
+        "Player owen made draw offer 1, the next one in game e2e-draw-replay is 2"
+    );
+    assert!(chain.game("e2e-draw-replay").await["draw_offer"].as_array().unwrap().is_empty());
+
+    // Nor can the receipt of an offer accept one
+    assert_eq!(pia.offer_draw().await, "OK");
+    let accept = CommunicationData { cmd: Command::AcceptDraw, ..owen.saved_receipt(Command::OfferDraw) };
+    assert_eq!(host::submit_data(&accept).await, "Receipt of player owen is for OfferDraw, not AcceptDraw");
+    assert_eq!(chain.game("e2e-draw-replay").await["draw_offers"], 2);
+    assert_eq!(owen.accept_draw().await, "OK");
+    events.expect("Game e2e-draw-replay ended in a draw");
+}
+
+#[tokio::test]
+async fn fake_receipt_of_another_method() {
+    let chain = chain();
+    let opal = Player::new("e2e-fake-method", "opal", 41);
+    let pete = Player::new("e2e-fake-method", "pete", 42);
+    assert_eq!(opal.join().await, "OK");
+    assert_eq!(pete.join().await, "OK");
+    assert_eq!(opal.wave().await, "OK");
+
+    // Pete's join receipt has the journal of a wave, but was made by the join method
+    let join = pete.saved_receipt(Command::Join);
+    let wave = CommunicationData { cmd: Command::Wave, ..join };
+    assert_eq!(host::submit_data(&wave).await, "Could not verify receipt");
+    assert_eq!(chain.game("e2e-fake-method").await["next_player"], "pete");
+}
+
+#[tokio::test]
+async fn garbage_journal() {
+    let chain = chain();
+    let quin = Player::new("e2e-garbage", "quin", 46);
+    let rosa = Player::new("e2e-garbage", "rosa", 47);
+    assert_eq!(quin.join().await, "OK");
+
+    // A journal that decodes to nothing is refused, and the node carries on
+    let mut garbage = quin.saved_receipt(Command::Join);
+    garbage.receipt.journal.bytes = vec![0xff; 3];
+    for cmd in [Command::Join, Command::Fire, Command::Report, Command::Resign] {
+        let data = CommunicationData { cmd, receipt: garbage.receipt.clone(), mode: None };
+        assert_eq!(host::submit_data(&data).await, "Could not verify receipt");
+    }
+    assert_eq!(rosa.join().await, "OK");
+    assert_eq!(chain.game("e2e-garbage").await["next_player"], "quin");
+}
@@ -1,76 +1,408 @@
-use serde::{Deserialize, Serialize};
-use risc0_zkvm::{Receipt, Digest};
-
-// Struct sent by the rust code for input on the methods join, wave and win
-// The struct is read by the zkvm code and the data is used to generate the output Journal
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct BaseInputs {
-    pub gameid: String,
-    pub fleet: String,
-    pub board: Vec<u8>,
-    pub random: String,
-}
-
-// Struct sent by the rust code for input on the methods fire
-// The struct is read by the zkvm code and the data is used to generate the output Journal
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct FireInputs {
-    pub gameid: String,
-    pub fleet: String,
-    pub board: Vec<u8>,
-    pub random: String,
-    pub target: String,
-    pub pos: u8,
-}
-
-// Struct sent by the rust code for input on the methods report
-// The struct is read by the zkvm code and the data is used to generate the output Journal
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct ReportInputs {
-    pub gameid: String,
-    pub fleet: String,
-    pub board: Vec<u8>,
-    pub random: String,
-    pub report: String,
-    pub pos: u8,
-}
-
-// Enum used to define the command that will be sent to the server by the host in the communication packet
-#[derive(Deserialize,Serialize)]
-pub enum Command {Join, Fire, Report, Wave, Win}
-
-// Struct used to specify the packet sent from the client to the blockchain server
-#[derive(Deserialize,Serialize)]
-pub struct CommunicationData {
-    pub cmd: Command,
-    pub receipt: Receipt,
-}
-
-// Struct to specify the  output journal for join, wave and win methods
-#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
-pub struct BaseJournal {
-    pub gameid: String,
-    pub fleet: String,
-    pub board: Digest,
-}
-
-// Struct to specify the  output journal for fire method
-#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
-pub struct FireJournal {
-    pub gameid: String,
-    pub fleet: String,
-    pub board: Digest,
-    pub target: String,
-    pub pos: u8,
-}
-
-// Struct to specify the  output journal for report method
-#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
-pub struct ReportJournal {
-    pub gameid: String,
-    pub fleet: String,
-    pub report: String,
-    pub pos: u8,
-    pub board: Digest,
-    pub next_board: Digest
-}
\ No newline at end of file
+use serde::{Deserialize, Serialize};
+use risc0_zkvm::{Receipt, Digest};
+use sha2::{Digest as ShaDigest, Sha256};
+
+mod placement;
+pub use placement::{
+    fleet_cells, move_ship, on_board, random_board, seeded_board, surviving_ships, Direction, BOARD_SIDE, FLEET_MINES, FLEET_SHIPS,
+};
+
+// Struct sent by the rust code for input on the methods join, wave and win
+// The struct is read by the zkvm code and the data is used to generate the output Journal
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BaseInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    // Team the fleet plays for, declared at join and ignored by wave and win
+    pub team: Option<String>,
+    // Hidden mines placed at join in a game played with them, ignored by wave and win
+    pub mines: Vec<u8>,
+}
+
+// Struct sent by the rust code for input on the methods fire
+// The struct is read by the zkvm code and the data is used to generate the output Journal
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FireInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    pub target: String,
+    // One shot, or up to one per surviving ship in a salvo game
+    pub positions: Vec<u8>,
+    // Board the fleet joined with, the ships still afloat are counted on it
+    pub origin_board: Vec<u8>,
+}
+
+// Struct sent by the rust code for input on the methods report
+// The struct is read by the zkvm code and the data is used to generate the output Journal
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReportInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    pub report: String,
+    pub pos: u8,
+    // Mines placed at join, none in a game played without them
+    pub mines: Vec<u8>,
+    // Journal of the fleet's previous join, report or move receipt, added as an assumption
+    pub history: BoardHistory,
+    // Image IDs of the join, report and move methods, the report verifies its predecessor with them
+    pub join_id: Digest,
+    pub report_id: Digest,
+    pub move_id: Digest,
+}
+
+// Struct sent by the rust code for input on the method batch_report, which reports
+// every shot of a salvo in one proof
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BatchReportInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    pub positions: Vec<u8>,
+    // Journal of the fleet's previous join or batch report receipt, added as an assumption
+    pub history: BoardHistory,
+    // Image IDs of the join and batch report methods, to verify the predecessor with
+    pub join_id: Digest,
+    pub batch_report_id: Digest,
+}
+
+// Struct sent by the rust code for input on the method scan, a fleet asking for a
+// radar scan of an area of the target's board
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScanInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    pub target: String,
+    pub area: ScanArea,
+}
+
+// Struct sent by the rust code for input on the method scan_report, the scanned
+// fleet answering with the number of ship cells in the area
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScanReportInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    pub area: ScanArea,
+}
+
+// Square of board cells a radar scan covers, from its top left cell
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScanArea {
+    pub pos: u8,
+    pub size: u8,
+}
+
+// Side of the area a scan covers unless the chain is set up otherwise
+pub const SCAN_SIZE: u8 = 3;
+
+impl ScanArea {
+    // The whole area lies on the board. Counted in usize, so no size overflows.
+    pub fn fits(&self) -> bool {
+        let (side, pos, size) = (BOARD_SIDE, self.pos as usize, self.size as usize);
+        size > 0 && pos < side * side && pos % side + size <= side && pos / side + size <= side
+    }
+
+    pub fn contains(&self, pos: u8) -> bool {
+        let (side, size) = (BOARD_SIDE, self.size as usize);
+        let (x, y) = (pos as usize % side, pos as usize / side);
+        let (left, top) = (self.pos as usize % side, self.pos as usize / side);
+        x >= left && x < left + size && y >= top && y < top + size
+    }
+
+    // Bottom right cell of the area
+    pub fn last(&self) -> u8 {
+        self.pos + (self.size - 1) * (BOARD_SIDE as u8 + 1)
+    }
+}
+
+// Struct sent by the rust code for input on the method move_ship, a fleet moving
+// one of its undamaged ships by one cell instead of firing
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MoveInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    // Cells of the fleet hit so far, public since they were reported
+    pub hits: Vec<u8>,
+    // Any cell of the ship to move, kept private
+    pub ship: u8,
+    pub direction: Direction,
+    // Mines placed at join, none in a game played without them. No ship moves onto one.
+    pub mines: Vec<u8>,
+    // Journal of the fleet's previous join, report or move receipt, added as an assumption
+    pub history: BoardHistory,
+    pub join_id: Digest,
+    pub report_id: Digest,
+    pub move_id: Digest,
+}
+
+// Struct sent by the rust code for input on the method ownership, which proves a
+// resignation, a draw offer or the acceptance of one comes from the fleet itself
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct OwnershipInputs {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Vec<u8>,
+    pub random: String,
+    // Resign, OfferDraw or AcceptDraw
+    pub action: Command,
+    // Number of the draw offer made or accepted, counting the game's offers from 1.
+    // The chain ignores it for a resignation.
+    pub offer: u32,
+}
+
+// Last proven state of a fleet's board before a report
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BoardHistory {
+    Joined(BaseJournal),
+    Reported(ReportJournal),
+    BatchReported(BatchReportJournal),
+    Moved(MoveJournal),
+}
+
+// Enum used to define the command that will be sent to the server by the host in the communication packet
+#[derive(Deserialize,Serialize,Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Command {Join, Fire, Report, BatchReport, Wave, Win, Scan, ScanReport, Move, Resign, OfferDraw, AcceptDraw}
+
+// Rules a game is played with, chosen by the fleet whose join creates the game
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum GameMode {
+    // One shot per turn
+    #[default]
+    Classic,
+    // One shot per surviving ship each turn, all reported at once
+    Salvo,
+    // One shot per turn, or an undamaged ship moved by one cell instead
+    Movable,
+}
+
+// Version of the guest methods and journals, bumped whenever they change in a way
+// that makes receipts from older methods unusable with newer ones
+pub const PROTOCOL_VERSION: u32 = 9;
+
+// Image IDs of the guest methods of one protocol version
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MethodSet {
+    pub protocol: u32,
+    pub join: Digest,
+    pub fire: Digest,
+    pub report: Digest,
+    // Added in protocol 2, method sets of older versions have none
+    #[serde(default)]
+    pub batch_report: Digest,
+    pub wave: Digest,
+    pub win: Digest,
+    // Added in protocol 4
+    #[serde(default)]
+    pub scan: Digest,
+    #[serde(default)]
+    pub scan_report: Digest,
+    // Added in protocol 5
+    #[serde(default)]
+    pub move_ship: Digest,
+    // Added in protocol 8, proves resignations and both sides of a draw
+    #[serde(default)]
+    pub ownership: Digest,
+}
+
+impl MethodSet {
+    // Image ID a receipt for the command must verify against
+    pub fn image_id(&self, cmd: Command) -> Digest {
+        match cmd {
+            Command::Join => self.join,
+            Command::Fire => self.fire,
+            Command::Report => self.report,
+            Command::BatchReport => self.batch_report,
+            Command::Wave => self.wave,
+            Command::Win => self.win,
+            Command::Scan => self.scan,
+            Command::ScanReport => self.scan_report,
+            Command::Move => self.move_ship,
+            Command::Resign | Command::OfferDraw | Command::AcceptDraw => self.ownership,
+        }
+    }
+}
+
+// Struct returned by the blockchain server's /version endpoint
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VersionInfo {
+    // Protocol version the server was built with
+    pub protocol: u32,
+    // Every method set the server verifies receipts against, its own first
+    pub accepted: Vec<MethodSet>,
+}
+
+// Struct used to specify the packet sent from the client to the blockchain server
+#[derive(Deserialize,Serialize)]
+pub struct CommunicationData {
+    pub cmd: Command,
+    pub receipt: Receipt,
+    // Mode of the game a join creates, any mode is fine when left out
+    #[serde(default)]
+    pub mode: Option<GameMode>,
+}
+
+// Struct posted to the blockchain server's /reveal endpoint once a game is over. It
+// opens the commitments the fleet made at join, so it needs no receipt.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RevealData {
+    pub gameid: String,
+    pub fleet: String,
+    // Board as the fleet joined with it, in the same order
+    pub board: Vec<u8>,
+    pub random: String,
+    // Mines placed at join, empty for a game without mines
+    #[serde(default)]
+    pub mines: Vec<u8>,
+}
+
+// Commitment the join method makes to the board or the mines, the hash of the
+// random nonce and the cells
+pub fn commitment(random: &str, cells: &[u8]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(random.as_bytes());
+    hasher.update(cells);
+    Digest::try_from(hasher.finalize().as_slice()).expect("Digest conversion failed")
+}
+
+// Struct to specify the  output journal for join, wave and win methods
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct BaseJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    // Team declared at join, none for a game without teams
+    pub team: Option<String>,
+    // Commitment to the mines placed at join, none for a game without mines
+    pub mines: Option<Digest>,
+}
+
+// Struct to specify the  output journal for fire method
+#[derive(Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct FireJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub target: String,
+    pub positions: Vec<u8>,
+    // Join commitment of the board the shots were counted on
+    pub origin: Digest,
+}
+
+// Struct to specify the  output journal for report method
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct ReportJournal {
+    pub gameid: String,
+    pub fleet: String,
+    // "Hit", "Miss", or "Mine" for a shot on one of the fleet's mines
+    pub report: String,
+    pub pos: u8,
+    pub board: Digest,
+    pub next_board: Digest,
+    // Mine commitment the shot was checked against, the chain compares it with the join
+    pub mines: Option<Digest>,
+    // Board commitment made at join, every report carries it forward
+    pub origin: Digest,
+    // Number of reports in the fleet's history, this one included
+    pub reports: u32,
+    // Image IDs the previous receipt was verified against, checked by the chain
+    pub join_id: Digest,
+    pub report_id: Digest,
+    pub move_id: Digest,
+}
+
+// Struct to specify the output journal for move method. Only the commitments are
+// public, not the ship that moved.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct MoveJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub next_board: Digest,
+    // Hit cells the move was checked against, the chain compares them with the reports
+    pub hits: Vec<u8>,
+    // Mine commitment the move was checked against, the chain compares it with the join
+    pub mines: Option<Digest>,
+    pub origin: Digest,
+    // A move is part of the fleet's history and counts along with its reports
+    pub reports: u32,
+    pub join_id: Digest,
+    pub report_id: Digest,
+    pub move_id: Digest,
+}
+
+// Struct to specify the output journal for batch report method
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct BatchReportJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub positions: Vec<u8>,
+    // "Hit" or "Miss" for every position, in the same order
+    pub results: Vec<String>,
+    pub board: Digest,
+    pub next_board: Digest,
+    pub origin: Digest,
+    pub reports: u32,
+    pub join_id: Digest,
+    pub batch_report_id: Digest,
+}
+
+// Struct to specify the output journal for ownership method. The action and offer
+// number keep the receipt from being replayed as another command or on a later offer.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct OwnershipJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub action: Command,
+    pub offer: u32,
+}
+
+// Struct to specify the output journal for scan method
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct ScanJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub target: String,
+    pub area: ScanArea,
+}
+
+// Struct to specify the output journal for scan report method, only the number
+// of ship cells in the area is revealed
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize, Default)]
+pub struct ScanReportJournal {
+    pub gameid: String,
+    pub fleet: String,
+    pub board: Digest,
+    pub area: ScanArea,
+    pub count: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_scan_areas_do_not_fit() {
+        assert!(ScanArea { pos: 0, size: 10 }.fits());
+        assert!(ScanArea { pos: 77, size: 3 }.fits());
+        assert!(!ScanArea { pos: 78, size: 3 }.fits());
+        assert!(!ScanArea { pos: 0, size: 0 }.fits());
+        // Sizes that wrap around in u8 arithmetic
+        assert!(!ScanArea { pos: 0, size: 255 }.fits());
+        assert!(!ScanArea { pos: 9, size: 250 }.fits());
+        assert!(!ScanArea { pos: 100, size: 1 }.fits());
+    }
+}
@@ -0,0 +1,357 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// Side of the square board, positions are encoded as y * BOARD_SIDE + x
+pub const BOARD_SIDE: usize = 10;
+
+// Ships every fleet must place: (ship size, number of ships of that size)
+pub const FLEET_SHIPS: [(usize, usize); 5] = [
+    (5, 1), // Carrier
+    (4, 1), // Battleship
+    (3, 1), // Destroyer
+    (2, 2), // Cruiser
+    (1, 2), // Submarine
+];
+
+// Mines every fleet places at join in a game played with them, off its ship cells
+pub const FLEET_MINES: usize = 3;
+
+// Whether the position is a cell of the board
+pub fn on_board(pos: u8) -> bool {
+    (pos as usize) < BOARD_SIDE * BOARD_SIDE
+}
+
+// Number of ship cells in a fleet, it is sunk once all of them were hit
+pub fn fleet_cells() -> usize {
+    FLEET_SHIPS.iter().map(|&(size, count)| size * count).sum()
+}
+
+// Generates a random board that satisfies the ship set and the spacing rule
+// (ships never touch each other, not even diagonally).
+// Every ship is dropped independently on a uniformly chosen spot and the whole
+// layout is thrown away on any conflict, so every valid layout is equally likely.
+pub fn random_board<R: Rng + ?Sized>(rng: &mut R) -> Vec<u8> {
+    loop {
+        if let Some(board) = try_place_fleet(rng) {
+            return board;
+        }
+    }
+}
+
+// Same as random_board but reproducible: the same seed always gives the same board
+pub fn seeded_board(seed: u64) -> Vec<u8> {
+    random_board(&mut StdRng::seed_from_u64(seed))
+}
+
+fn try_place_fleet<R: Rng + ?Sized>(rng: &mut R) -> Option<Vec<u8>> {
+    let mut grid = [[false; BOARD_SIDE]; BOARD_SIDE];
+
+    for &(size, count) in FLEET_SHIPS.iter() {
+        for _ in 0..count {
+            // A single cell ship looks the same in both directions, so it only gets one
+            let horizontal = size == 1 || rng.gen_bool(0.5);
+            let (x, y) = if horizontal {
+                (rng.gen_range(0..=BOARD_SIDE - size), rng.gen_range(0..BOARD_SIDE))
+            } else {
+                (rng.gen_range(0..BOARD_SIDE), rng.gen_range(0..=BOARD_SIDE - size))
+            };
+            let cells: Vec<(usize, usize)> = (0..size)
+                .map(|i| if horizontal { (x + i, y) } else { (x, y + i) })
+                .collect();
+
+            if cells.iter().any(|&(cx, cy)| touches_ship(&grid, cx, cy)) {
+                return None;
+            }
+            for (cx, cy) in cells {
+                grid[cy][cx] = true;
+            }
+        }
+    }
+
+    let board = (0..BOARD_SIDE * BOARD_SIDE)
+        .filter(|&pos| grid[pos / BOARD_SIDE][pos % BOARD_SIDE])
+        .map(|pos| pos as u8)
+        .collect();
+    Some(board)
+}
+
+// Checks the cell and its 8 neighbours for an already placed ship
+fn touches_ship(grid: &[[bool; BOARD_SIDE]; BOARD_SIDE], x: usize, y: usize) -> bool {
+    let rows = y.saturating_sub(1)..=(y + 1).min(BOARD_SIDE - 1);
+    let cols = x.saturating_sub(1)..=(x + 1).min(BOARD_SIDE - 1);
+    grid[rows].iter().any(|row| row[cols.clone()].iter().any(|&cell| cell))
+}
+
+// Number of ships of the joined board that still have a cell left on the current
+// board. Ships never touch, so every group of adjacent cells is one ship.
+pub fn surviving_ships(origin: &[u8], board: &[u8]) -> usize {
+    let mut seen = Vec::new();
+    let mut surviving = 0;
+    for &start in origin {
+        if seen.contains(&start) {
+            continue;
+        }
+        // Walk the ship the cell belongs to
+        let mut afloat = false;
+        let mut stack = vec![start];
+        seen.push(start);
+        while let Some(pos) = stack.pop() {
+            afloat |= board.contains(&pos);
+            let (x, y) = (pos as usize % BOARD_SIDE, pos as usize / BOARD_SIDE);
+            let neighbours = [
+                (x > 0).then(|| pos - 1),
+                (x + 1 < BOARD_SIDE).then(|| pos + 1),
+                (y > 0).then(|| pos - BOARD_SIDE as u8),
+                (y + 1 < BOARD_SIDE).then(|| pos + BOARD_SIDE as u8),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if origin.contains(&next) && !seen.contains(&next) {
+                    seen.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        if afloat {
+            surviving += 1;
+        }
+    }
+    surviving
+}
+
+// Way a ship is moved, by one cell
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// Moves the undamaged ship with a cell at the given position by one cell. Hit cells
+// are no longer on the board but still belong to their ships, so the layout the
+// spacing rule applies to is the board with the hits. Ships stay off the fleet's
+// mines, as at join. Returns the new board, sorted.
+pub fn move_ship(board: &[u8], hits: &[u8], mines: &[u8], ship: u8, direction: Direction) -> Result<Vec<u8>, String> {
+    if !board.contains(&ship) {
+        return Err(format!("No ship afloat at position {}", ship));
+    }
+    let layout: Vec<u8> = board.iter().chain(hits).copied().collect();
+
+    // Walk the ship the cell belongs to
+    let mut cells = vec![ship];
+    let mut i = 0;
+    while i < cells.len() {
+        let pos = cells[i] as usize;
+        let (x, y) = (pos % BOARD_SIDE, pos / BOARD_SIDE);
+        let neighbours = [
+            (x > 0).then(|| pos - 1),
+            (x + 1 < BOARD_SIDE).then(|| pos + 1),
+            (y > 0).then(|| pos - BOARD_SIDE),
+            (y + 1 < BOARD_SIDE).then(|| pos + BOARD_SIDE),
+        ];
+        for next in neighbours.into_iter().flatten().map(|next| next as u8) {
+            if layout.contains(&next) && !cells.contains(&next) {
+                cells.push(next);
+            }
+        }
+        i += 1;
+    }
+    if cells.iter().any(|pos| hits.contains(pos)) {
+        return Err(format!("The ship at position {} was hit and can't move", ship));
+    }
+
+    let moved: Vec<u8> = cells
+        .iter()
+        .map(|&pos| {
+            let (x, y) = (pos as usize % BOARD_SIDE, pos as usize / BOARD_SIDE);
+            let (x, y) = match direction {
+                Direction::Up if y > 0 => (x, y - 1),
+                Direction::Down if y + 1 < BOARD_SIDE => (x, y + 1),
+                Direction::Left if x > 0 => (x - 1, y),
+                Direction::Right if x + 1 < BOARD_SIDE => (x + 1, y),
+                _ => return Err(format!("The ship at position {} can't move off the board", ship)),
+            };
+            Ok((y * BOARD_SIDE + x) as u8)
+        })
+        .collect::<Result<_, String>>()?;
+    if moved.iter().any(|pos| mines.contains(pos)) {
+        return Err(format!("The ship at position {} would move onto a mine", ship));
+    }
+
+    // Ships still never touch, not even diagonally
+    let mut grid = [[false; BOARD_SIDE]; BOARD_SIDE];
+    for &pos in layout.iter().filter(|pos| !cells.contains(pos)) {
+        grid[pos as usize / BOARD_SIDE][pos as usize % BOARD_SIDE] = true;
+    }
+    if moved.iter().any(|&pos| touches_ship(&grid, pos as usize % BOARD_SIDE, pos as usize / BOARD_SIDE)) {
+        return Err(format!("The ship at position {} would touch another ship", ship));
+    }
+
+    let mut next: Vec<u8> = board.iter().filter(|pos| !cells.contains(pos)).copied().chain(moved).collect();
+    next.sort();
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ships of the board as groups of adjacent cells, each sorted
+    fn ships(board: &[u8]) -> Vec<Vec<u8>> {
+        let mut ships: Vec<Vec<u8>> = Vec::new();
+        for &pos in board {
+            if ships.iter().any(|ship| ship.contains(&pos)) {
+                continue;
+            }
+            let mut ship = vec![pos];
+            let mut i = 0;
+            while i < ship.len() {
+                let (x, y) = (ship[i] as usize % BOARD_SIDE, ship[i] as usize / BOARD_SIDE);
+                for &other in board {
+                    let (ox, oy) = (other as usize % BOARD_SIDE, other as usize / BOARD_SIDE);
+                    if x.abs_diff(ox) + y.abs_diff(oy) == 1 && !ship.contains(&other) {
+                        ship.push(other);
+                    }
+                }
+                i += 1;
+            }
+            ship.sort();
+            ships.push(ship);
+        }
+        ships
+    }
+
+    // Cells of different ships are never neighbours, not even diagonally
+    fn spaced(board: &[u8]) -> bool {
+        let ships = ships(board);
+        ships.iter().enumerate().all(|(i, ship)| {
+            ships[i + 1..].iter().flatten().all(|&other| {
+                ship.iter().all(|&pos| {
+                    let (x, y) = (pos as usize % BOARD_SIDE, pos as usize / BOARD_SIDE);
+                    let (ox, oy) = (other as usize % BOARD_SIDE, other as usize / BOARD_SIDE);
+                    x.abs_diff(ox) > 1 || y.abs_diff(oy) > 1
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn boards_hold_the_fleet() {
+        let mut expected: Vec<usize> = FLEET_SHIPS.iter().flat_map(|&(size, count)| vec![size; count]).collect();
+        expected.sort();
+        for seed in 0..500 {
+            let board = seeded_board(seed);
+            assert_eq!(board.len(), fleet_cells(), "seed {}", seed);
+            assert!(board.windows(2).all(|pair| pair[0] < pair[1]), "seed {}", seed);
+            assert!(board.iter().all(|&pos| on_board(pos)), "seed {}", seed);
+            assert!(spaced(&board), "seed {}", seed);
+
+            // Every ship is a straight line of the sizes the fleet is made of
+            let ships = ships(&board);
+            let mut sizes: Vec<usize> = ships.iter().map(Vec::len).collect();
+            sizes.sort();
+            assert_eq!(sizes, expected, "seed {}", seed);
+            for ship in &ships {
+                let row = ship.iter().all(|&pos| pos / BOARD_SIDE as u8 == ship[0] / BOARD_SIDE as u8);
+                let column = ship.iter().all(|&pos| pos % BOARD_SIDE as u8 == ship[0] % BOARD_SIDE as u8);
+                assert!(row || column, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_board() {
+        for seed in 0..50 {
+            assert_eq!(seeded_board(seed), seeded_board(seed));
+        }
+        assert_ne!(seeded_board(1), seeded_board(2));
+        let board = random_board(&mut StdRng::seed_from_u64(7));
+        assert_eq!(board, seeded_board(7));
+    }
+
+    #[test]
+    fn ships_survive_until_every_cell_is_hit() {
+        // A three cell ship on row 0, a two cell ship down column 0 and a submarine
+        let origin = [0, 1, 2, 5, 20, 30];
+        assert_eq!(surviving_ships(&origin, &origin), 3);
+        // Partly hit ships are still afloat
+        assert_eq!(surviving_ships(&origin, &[0, 2, 5, 30]), 3);
+        // A ship with every cell hit is sunk
+        assert_eq!(surviving_ships(&origin, &[5, 20, 30]), 2);
+        assert_eq!(surviving_ships(&origin, &[1]), 1);
+        assert_eq!(surviving_ships(&origin, &[]), 0);
+    }
+
+    #[test]
+    fn nearby_ships_count_apart() {
+        // One empty cell apart on a row, and diagonal only at a corner
+        let origin = [0, 1, 3, 4, 15, 26];
+        assert_eq!(surviving_ships(&origin, &origin), 4);
+        assert_eq!(surviving_ships(&origin, &[3, 4, 26]), 2);
+        // Ships that touched would be one, which is why the join keeps them apart
+        assert_eq!(surviving_ships(&[0, 1, 2, 3], &[0, 1, 2, 3]), 1);
+    }
+
+    #[test]
+    fn ships_move_one_cell_each_way() {
+        // A two cell ship in the middle of the board
+        let board = [44, 45];
+        assert_eq!(move_ship(&board, &[], &[], 44, Direction::Up), Ok(vec![34, 35]));
+        assert_eq!(move_ship(&board, &[], &[], 45, Direction::Down), Ok(vec![54, 55]));
+        assert_eq!(move_ship(&board, &[], &[], 44, Direction::Left), Ok(vec![43, 44]));
+        assert_eq!(move_ship(&board, &[], &[], 45, Direction::Right), Ok(vec![45, 46]));
+        assert_eq!(
+            move_ship(&board, &[], &[], 50, Direction::Up),
+            Err("No ship afloat at position 50".to_string())
+        );
+    }
+
+    #[test]
+    fn ships_stay_on_the_board() {
+        let board = [0, 1, 98, 99];
+        for (ship, direction) in [(0, Direction::Up), (1, Direction::Left), (98, Direction::Down), (99, Direction::Right)] {
+            assert_eq!(
+                move_ship(&board, &[], &[], ship, direction),
+                Err(format!("The ship at position {} can't move off the board", ship))
+            );
+        }
+    }
+
+    #[test]
+    fn moved_ships_keep_their_distance() {
+        // The ship on row 0 would end up next to the submarine at B2, also diagonally
+        assert_eq!(
+            move_ship(&[0, 1, 21], &[], &[], 0, Direction::Down),
+            Err("The ship at position 0 would touch another ship".to_string())
+        );
+        assert_eq!(
+            move_ship(&[0, 1, 22], &[], &[], 1, Direction::Down),
+            Err("The ship at position 1 would touch another ship".to_string())
+        );
+        // Hit cells still belong to their ship, so they keep others away too
+        assert_eq!(
+            move_ship(&[0, 1], &[21], &[], 0, Direction::Down),
+            Err("The ship at position 0 would touch another ship".to_string())
+        );
+    }
+
+    #[test]
+    fn hit_ships_stay_put() {
+        assert_eq!(
+            move_ship(&[44], &[45], &[], 44, Direction::Up),
+            Err("The ship at position 44 was hit and can't move".to_string())
+        );
+        // A hit on another ship does not hold this one back
+        assert_eq!(move_ship(&[0, 1, 55], &[56], &[], 0, Direction::Right), Ok(vec![1, 2, 55]));
+    }
+
+    #[test]
+    fn ships_stay_off_mines() {
+        // A two cell ship in the corner with a mine under its right end
+        assert_eq!(
+            move_ship(&[0, 1], &[], &[11], 0, Direction::Down),
+            Err("The ship at position 0 would move onto a mine".to_string())
+        );
+        assert_eq!(move_ship(&[0, 1], &[], &[11], 0, Direction::Right), Ok(vec![1, 2]));
+    }
+}
@@ -0,0 +1,677 @@
+// src/bot.rs
+
+use fleetcore::{random_board, surviving_ships, GameMode, ScanArea, BOARD_SIDE, FLEET_SHIPS};
+use futures::StreamExt;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::{answer_scan, chain_url, check_methods, fire, game_state, join_game, report, report_salvo, win, FormData};
+
+// How hard the bot tries to find the opponent's ships
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    // Fires at any cell it has not tried yet
+    Easy,
+    // Hunts on a checkerboard pattern and finishes off ships it has hit
+    Medium,
+    // Fires where the most ship placements fit what it knows so far
+    Hard,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("Unknown difficulty {}, use easy, medium or hard", s)),
+        }
+    }
+}
+
+pub struct BotConfig {
+    pub gameid: String,
+    pub fleet: String,
+    pub difficulty: Difficulty,
+    // Makes the placement and the shots reproducible
+    pub seed: Option<u64>,
+    // Mode of the game if our join creates it
+    pub mode: Option<GameMode>,
+    // Team we declare at join, for a game played in teams
+    pub team: Option<String>,
+}
+
+// What the bot knows about one cell of an opponent's board
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Unknown,
+    Miss,
+    Hit,
+    // Diagonal to a hit, so the spacing rule says no ship can be there
+    Blocked,
+}
+
+// Messages published by the chain that the bot reacts to
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ChainEvent {
+    Joined { fleet: String, gameid: String, team: Option<String> },
+    Fired { fleet: String, target: String, positions: Vec<u8> },
+    Reported { fleet: String, results: Vec<String>, positions: Vec<u8> },
+    Scanned { fleet: String, target: String, area: ScanArea },
+    Turn { fleet: String, gameid: String },
+    Eliminated { fleet: String, gameid: String },
+    LastStanding { fleet: String, gameid: String },
+    TeamStanding { team: String, gameid: String },
+    Victory { fleet: String },
+    Drawn { gameid: String },
+    // Published when the game ended without a victory claim, say on a resignation
+    Over { gameid: String },
+}
+
+// Turns "B3" back into the position it was formatted from
+fn parse_pos(xy: &str) -> Option<u8> {
+    let mut chars = xy.chars();
+    let x = chars.next().filter(|c| ('A'..='J').contains(c))? as u8 - b'A';
+    let y = chars.next().filter(|c| c.is_ascii_digit())? as u8 - b'0';
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(y * 10 + x)
+}
+
+// Turns "B3,C4" back into positions
+fn parse_positions(list: &str) -> Option<Vec<u8>> {
+    list.split(',').map(parse_pos).collect()
+}
+
+// Formats a position the way the page and the chain do, e.g. "B3"
+fn xy(pos: u8) -> String {
+    format!("{}{}", (b'A' + pos % 10) as char, pos / 10)
+}
+
+// Turns "B3:D5" back into the scanned area
+fn parse_area(corners: &str) -> Option<ScanArea> {
+    let (first, last) = corners.split_once(':')?;
+    let (pos, last) = (parse_pos(first)?, parse_pos(last)?);
+    let size = (last % 10).checked_sub(pos % 10)? + 1;
+    Some(ScanArea { pos, size })
+}
+
+pub(crate) fn parse_event(msg: &str) -> Option<ChainEvent> {
+    // Accepted transactions are tagged with their receipt kind, e.g. "[succinct] Player ..."
+    let msg = match msg.strip_prefix('[').and_then(|rest| rest.split_once("] ")) {
+        Some((_, rest)) => rest,
+        None => msg,
+    };
+    let words: Vec<&str> = msg.split_whitespace().collect();
+    match words.as_slice() {
+        ["Player", fleet, "joined", "game", gameid] => Some(ChainEvent::Joined {
+            fleet: fleet.to_string(),
+            gameid: gameid.to_string(),
+            team: None,
+        }),
+        ["Player", fleet, "joined", "game", gameid, "for", "team", team] => Some(ChainEvent::Joined {
+            fleet: fleet.to_string(),
+            gameid: gameid.to_string(),
+            team: Some(team.to_string()),
+        }),
+        ["Player", fleet, "fired", "at", "player", target, "at", "pos", positions] => Some(ChainEvent::Fired {
+            fleet: fleet.to_string(),
+            target: target.to_string(),
+            positions: parse_positions(positions)?,
+        }),
+        ["Player", fleet, "reported", results, "at", "pos", positions] => Some(ChainEvent::Reported {
+            fleet: fleet.to_string(),
+            results: results.split(',').map(|r| r.to_string()).collect(),
+            positions: parse_positions(positions)?,
+        }),
+        ["Player", fleet, "scanned", "player", target, "at", corners] => Some(ChainEvent::Scanned {
+            fleet: fleet.to_string(),
+            target: target.to_string(),
+            area: parse_area(corners)?,
+        }),
+        ["Player", fleet, "to", "fire", "in", "game", gameid] => Some(ChainEvent::Turn {
+            fleet: fleet.to_string(),
+            gameid: gameid.to_string(),
+        }),
+        ["Player", fleet, "eliminated", "from", "game", gameid] => Some(ChainEvent::Eliminated {
+            fleet: fleet.to_string(),
+            gameid: gameid.to_string(),
+        }),
+        ["Player", fleet, "is", "the", "last", "fleet", "standing", "in", "game", gameid] => {
+            Some(ChainEvent::LastStanding {
+                fleet: fleet.to_string(),
+                gameid: gameid.to_string(),
+            })
+        }
+        ["Team", team, "is", "the", "last", "team", "standing", "in", "game", gameid] => {
+            Some(ChainEvent::TeamStanding {
+                team: team.to_string(),
+                gameid: gameid.to_string(),
+            })
+        }
+        ["Player", fleet, "claimed", "victory", ..] => Some(ChainEvent::Victory {
+            fleet: fleet.to_string(),
+        }),
+        ["Game", gameid, "ended", "in", "a", "draw"] => Some(ChainEvent::Drawn {
+            gameid: gameid.to_string(),
+        }),
+        ["Game", gameid, "is", "over,", ..] => Some(ChainEvent::Over {
+            gameid: gameid.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+struct Bot {
+    config: BotConfig,
+    rng: StdRng,
+    board: Vec<u8>,
+    // Board we joined with, in a salvo game we fire once for each of its ships afloat
+    origin: Vec<u8>,
+    random: String,
+    salvo: bool,
+    // What we know of every opponent's board, by fleet
+    opponents: HashMap<String, [Cell; 100]>,
+    // Fleets that joined for our team, they are never shot at
+    teammates: HashSet<String>,
+    // Opponents sunk by anyone, they are no longer shot at
+    eliminated: HashSet<String>,
+    // Target and positions of our last shots until the target reports them
+    pending_shots: Option<(String, Vec<u8>)>,
+    // Our own join was the first one seen for the game, so we shoot first
+    first_to_join: bool,
+}
+
+impl Bot {
+    fn new(config: BotConfig) -> Self {
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let board = random_board(&mut rng);
+        let random: String = (0..12).map(|_| rng.sample(rand::distributions::Alphanumeric) as char).collect();
+        Bot {
+            config,
+            rng,
+            origin: board.clone(),
+            board,
+            random,
+            salvo: false,
+            opponents: HashMap::new(),
+            teammates: HashSet::new(),
+            eliminated: HashSet::new(),
+            pending_shots: None,
+            first_to_join: false,
+        }
+    }
+
+    fn log(&self, msg: &str) {
+        println!("[bot {}] {}", self.config.fleet, msg);
+    }
+
+    fn form(&self, button: &str) -> FormData {
+        FormData {
+            button: button.to_string(),
+            gameid: Some(self.config.gameid.clone()),
+            fleetid: Some(self.config.fleet.clone()),
+            targetfleet: None,
+            x: None,
+            y: None,
+            rx: None,
+            ry: None,
+            report: None,
+            board: Some(
+                self.board
+                    .iter()
+                    .map(|pos| pos.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            shots: None,
+            random: Some(self.random.clone()),
+            seed: None,
+            mode: None,
+            salvo: None,
+            team: self.config.team.clone(),
+            size: None,
+            direction: None,
+            mines: None,
+        }
+    }
+
+    fn add_opponent(&mut self, fleet: &str) {
+        if fleet != self.config.fleet && !self.teammates.contains(fleet) {
+            self.opponents
+                .entry(fleet.to_string())
+                .or_insert([Cell::Unknown; 100]);
+        }
+    }
+
+    fn is_player(&self, fleet: &str) -> bool {
+        fleet == self.config.fleet || self.opponents.contains_key(fleet) || self.teammates.contains(fleet)
+    }
+
+    async fn join(&mut self) -> Result<(), String> {
+        let mut data = self.form("Join");
+        data.mode = self.config.mode.map(|mode| format!("{:?}", mode));
+        let response = join_game(data).await;
+        if response != "OK" {
+            return Err(response);
+        }
+
+        // The game may have been created by another fleet, ask the chain how it is played
+        let game = game_state(&self.config.gameid).await?;
+        self.salvo = game["mode"] == "Salvo";
+        self.log(&format!("joined game {} played in {} mode", self.config.gameid, game["mode"]));
+        Ok(())
+    }
+
+    async fn claim_victory(&mut self) -> Result<(), String> {
+        let response = win(self.form("Win")).await;
+        if response != "OK" {
+            return Err(response);
+        }
+        self.log("claimed victory");
+        Ok(())
+    }
+
+    async fn take_turn(&mut self) -> Result<(), String> {
+        let shots = if self.salvo { surviving_ships(&self.origin, &self.board) } else { 1 };
+        let Some((target, positions)) = self.choose_shots(shots) else {
+            return Err("No opponent to fire at".to_string());
+        };
+        let mut data = self.form("Fire");
+        data.targetfleet = Some(target.clone());
+        data.salvo = Some(positions.iter().map(|&pos| xy(pos)).collect::<Vec<String>>().join(","));
+        let response = fire(data).await;
+        if response != "OK" {
+            return Err(response);
+        }
+        self.log(&format!("fired at {} on {:?}", target, positions));
+        self.pending_shots = Some((target, positions));
+        Ok(())
+    }
+
+    async fn answer_shots(&mut self, positions: &[u8]) -> Result<(), String> {
+        let response = if self.salvo {
+            let mut data = self.form("ReportSalvo");
+            data.salvo = Some(positions.iter().map(|&pos| xy(pos)).collect::<Vec<String>>().join(","));
+            report_salvo(data).await
+        } else {
+            let pos = positions[0];
+            let hit = self.board.contains(&pos);
+            let mut data = self.form("Report");
+            data.report = Some(if hit { "Hit" } else { "Miss" }.to_string());
+            data.rx = Some(((b'A' + pos % 10) as char).to_string());
+            data.ry = Some((pos / 10).to_string());
+            report(data).await
+        };
+        if response != "OK" {
+            return Err(response);
+        }
+        // The report guests remove hit cells, keep our copy of the board in step
+        self.board.retain(|p| !positions.contains(p));
+        Ok(())
+    }
+
+    // The scan guest only reveals how many of our ship cells lie in the area
+    async fn answer_scan(&mut self, area: ScanArea) -> Result<(), String> {
+        let mut data = self.form("AnswerScan");
+        data.rx = Some(((b'A' + area.pos % 10) as char).to_string());
+        data.ry = Some((area.pos / 10).to_string());
+        data.size = Some(area.size.to_string());
+        let response = answer_scan(data).await;
+        if response != "OK" {
+            return Err(response);
+        }
+        Ok(())
+    }
+
+    // Picks a target, then as many different cells on its board as there are shots
+    fn choose_shots(&mut self, shots: usize) -> Option<(String, Vec<u8>)> {
+        let (target, mut grid) = self.choose_target()?;
+        let mut positions = Vec::new();
+        for _ in 0..shots {
+            let Some(pos) = self.choose_cell(&grid) else {
+                break;
+            };
+            // Not chosen twice, whatever the shot turns out to be
+            grid[pos as usize] = Cell::Miss;
+            positions.push(pos);
+        }
+        if positions.is_empty() {
+            return None;
+        }
+        Some((target, positions))
+    }
+
+    fn choose_cell(&mut self, grid: &[Cell; 100]) -> Option<u8> {
+        match self.config.difficulty {
+            Difficulty::Easy => random_shot(grid, &mut self.rng, false),
+            Difficulty::Medium => target_shot(grid, &mut self.rng)
+                .or_else(|| random_shot(grid, &mut self.rng, true)),
+            Difficulty::Hard => density_shot(grid, &mut self.rng),
+        }
+    }
+
+    // Picks the opponent with the most cells left to find
+    fn choose_target(&self) -> Option<(String, [Cell; 100])> {
+        let mut targets: Vec<(&String, &[Cell; 100])> = self
+            .opponents
+            .iter()
+            .filter(|(fleet, _)| !self.eliminated.contains(*fleet))
+            .collect();
+        targets.sort_by_key(|(fleet, grid)| {
+            (grid.iter().filter(|&&c| c == Cell::Hit).count(), fleet.to_string())
+        });
+        let (target, grid) = targets.last()?;
+        Some((target.to_string(), **grid))
+    }
+
+    fn record_result(&mut self, target: &str, pos: u8, hit: bool) {
+        let Some(grid) = self.opponents.get_mut(target) else {
+            return;
+        };
+        let (x, y) = ((pos % 10) as i32, (pos / 10) as i32);
+        if hit {
+            grid[pos as usize] = Cell::Hit;
+            for (dx, dy) in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+                if let Some(n) = neighbour(x + dx, y + dy) {
+                    if grid[n] == Cell::Unknown {
+                        grid[n] = Cell::Blocked;
+                    }
+                }
+            }
+        } else {
+            grid[pos as usize] = Cell::Miss;
+        }
+    }
+
+    // Reacts to one chain message, returns false once the game is over
+    async fn handle(&mut self, event: ChainEvent) -> bool {
+        let me = self.config.fleet.clone();
+        let result = match event {
+            ChainEvent::Joined { fleet, gameid, team } if gameid == self.config.gameid => {
+                if fleet == me {
+                    self.first_to_join = self.opponents.is_empty() && self.teammates.is_empty();
+                    Ok(())
+                } else if team.is_some() && team == self.config.team {
+                    self.teammates.insert(fleet);
+                    Ok(())
+                } else {
+                    let first_opponent = self.opponents.is_empty();
+                    self.add_opponent(&fleet);
+                    // The first fleet to join takes the first shot
+                    if first_opponent && self.first_to_join {
+                        self.take_turn().await
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+            ChainEvent::Fired { fleet, target, positions } if self.is_player(&fleet) || self.is_player(&target) => {
+                self.add_opponent(&fleet);
+                self.add_opponent(&target);
+                if target == me {
+                    self.answer_shots(&positions).await
+                } else {
+                    Ok(())
+                }
+            }
+            ChainEvent::Scanned { fleet, target, area } if target == me => {
+                self.add_opponent(&fleet);
+                self.answer_scan(area).await
+            }
+            ChainEvent::Reported { fleet, results, positions } => {
+                if self.pending_shots.as_ref() == Some(&(fleet.clone(), positions.clone())) {
+                    self.pending_shots = None;
+                    for (&pos, result) in positions.iter().zip(&results) {
+                        self.record_result(&fleet, pos, result == "Hit");
+                    }
+                }
+                Ok(())
+            }
+            // The turn goes round the fleets afloat, whoever was shot at
+            ChainEvent::Turn { fleet, gameid } if fleet == me && gameid == self.config.gameid => {
+                self.take_turn().await
+            }
+            ChainEvent::Eliminated { fleet, gameid } if gameid == self.config.gameid => {
+                if fleet == me {
+                    self.log("all our ships are sunk");
+                    return false;
+                }
+                self.eliminated.insert(fleet);
+                Ok(())
+            }
+            ChainEvent::LastStanding { fleet, gameid } if fleet == me && gameid == self.config.gameid => {
+                self.claim_victory().await
+            }
+            // Any of us still afloat can claim it for the team, the first claim wins
+            ChainEvent::TeamStanding { team, gameid }
+                if self.config.team.as_ref() == Some(&team) && gameid == self.config.gameid =>
+            {
+                self.claim_victory().await
+            }
+            ChainEvent::Victory { fleet } if self.is_player(&fleet) => {
+                self.log(&format!("{} claimed victory, game over", fleet));
+                return false;
+            }
+            ChainEvent::Drawn { gameid } if gameid == self.config.gameid => {
+                self.log("the game ended in a draw");
+                return false;
+            }
+            ChainEvent::Over { gameid } if gameid == self.config.gameid => {
+                self.log("the game is over");
+                return false;
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            self.log(&err);
+        }
+        true
+    }
+}
+
+fn neighbour(x: i32, y: i32) -> Option<usize> {
+    let side = BOARD_SIDE as i32;
+    if x >= 0 && x < side && y >= 0 && y < side {
+        Some((y * side + x) as usize)
+    } else {
+        None
+    }
+}
+
+// Any cell not tried yet, optionally only on one colour of a checkerboard since
+// every ship longer than one cell covers both colours
+fn random_shot(grid: &[Cell; 100], rng: &mut StdRng, parity: bool) -> Option<u8> {
+    let open: Vec<u8> = (0..100u8)
+        .filter(|&p| grid[p as usize] == Cell::Unknown)
+        .collect();
+    let even: Vec<u8> = open.iter().copied().filter(|p| (p % 10 + p / 10) % 2 == 0).collect();
+    if parity && !even.is_empty() {
+        even.choose(rng).copied()
+    } else {
+        open.choose(rng).copied()
+    }
+}
+
+// Cells next to a hit, preferring the ones that continue a line of hits
+fn target_shot(grid: &[Cell; 100], rng: &mut StdRng) -> Option<u8> {
+    let mut candidates = Vec::new();
+    let mut in_line = Vec::new();
+    for pos in 0..100 {
+        if grid[pos] != Cell::Hit {
+            continue;
+        }
+        let (x, y) = ((pos % 10) as i32, (pos / 10) as i32);
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let Some(n) = neighbour(x + dx, y + dy) else {
+                continue;
+            };
+            if grid[n] != Cell::Unknown {
+                continue;
+            }
+            candidates.push(n as u8);
+            if neighbour(x - dx, y - dy).is_some_and(|b| grid[b] == Cell::Hit) {
+                in_line.push(n as u8);
+            }
+        }
+    }
+    in_line.choose(rng).or_else(|| candidates.choose(rng)).copied()
+}
+
+// Counts, for every open cell, how many placements of the fleet's ships could
+// cover it; placements through known hits count for much more
+fn density_shot(grid: &[Cell; 100], rng: &mut StdRng) -> Option<u8> {
+    const HIT_WEIGHT: u32 = 20;
+    let mut weights = [0u32; 100];
+    for &(size, count) in FLEET_SHIPS.iter() {
+        let directions: &[(usize, usize)] = if size == 1 { &[(1, 0)] } else { &[(1, 0), (0, 1)] };
+        for &(dx, dy) in directions {
+            for y in 0..=(BOARD_SIDE - 1 - dy * (size - 1)) {
+                for x in 0..=(BOARD_SIDE - 1 - dx * (size - 1)) {
+                    let cells: Vec<usize> = (0..size)
+                        .map(|i| (y + dy * i) * BOARD_SIDE + x + dx * i)
+                        .collect();
+                    if cells.iter().any(|&c| matches!(grid[c], Cell::Miss | Cell::Blocked)) {
+                        continue;
+                    }
+                    let hits = cells.iter().filter(|&&c| grid[c] == Cell::Hit).count() as u32;
+                    let weight = (1 + HIT_WEIGHT * hits) * count as u32;
+                    for &c in cells.iter().filter(|&&c| grid[c] == Cell::Unknown) {
+                        weights[c] += weight;
+                    }
+                }
+            }
+        }
+    }
+    let best = *weights.iter().max()?;
+    if best == 0 {
+        return random_shot(grid, rng, false);
+    }
+    let top: Vec<u8> = (0..100u8).filter(|&p| weights[p as usize] == best).collect();
+    top.choose(rng).copied()
+}
+
+// Joins the game and plays it to the end following the chain's event stream
+pub async fn run_bot(config: BotConfig) -> Result<(), String> {
+    check_methods().await?;
+    let mut bot = Bot::new(config);
+
+    bot.join().await?;
+
+    // The game's stream replays it from the start, so nothing before our join is missed
+    let response = reqwest::get(format!("{}/games/{}/events", chain_url(), bot.config.gameid))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("Could not follow game events: {}", err))?;
+    let mut stream = response.bytes_stream();
+
+    let mut buffer = String::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| format!("Chain event stream failed: {}", err))?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find('\n') {
+            let line: String = buffer.drain(..=end).collect();
+            let Some(msg) = line.trim_end().strip_prefix("data:") else {
+                continue;
+            };
+            if let Some(event) = parse_event(msg.trim_start()) {
+                if !bot.handle(event).await {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err("Chain event stream closed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with(cells: &[(u8, Cell)]) -> [Cell; 100] {
+        let mut grid = [Cell::Unknown; 100];
+        for &(pos, cell) in cells {
+            grid[pos as usize] = cell;
+        }
+        grid
+    }
+
+    #[test]
+    fn parses_chain_messages() {
+        assert_eq!(
+            parse_event("[succinct] Player alice joined game g1 for team red"),
+            Some(ChainEvent::Joined { fleet: "alice".into(), gameid: "g1".into(), team: Some("red".into()) })
+        );
+        assert_eq!(
+            parse_event("[fake] Player alice fired at player bob at pos B3,C4"),
+            Some(ChainEvent::Fired { fleet: "alice".into(), target: "bob".into(), positions: vec![31, 42] })
+        );
+        assert_eq!(
+            parse_event("Player bob reported Hit,Miss at pos B3,C4"),
+            Some(ChainEvent::Reported {
+                fleet: "bob".into(),
+                results: vec!["Hit".into(), "Miss".into()],
+                positions: vec![31, 42],
+            })
+        );
+        assert_eq!(
+            parse_event("Player alice scanned player bob at B3:D5"),
+            Some(ChainEvent::Scanned { fleet: "alice".into(), target: "bob".into(), area: ScanArea { pos: 31, size: 3 } })
+        );
+        assert_eq!(
+            parse_event("[fake] Player alice claimed victory for team red"),
+            Some(ChainEvent::Victory { fleet: "alice".into() })
+        );
+        assert_eq!(parse_event("Game g1 ended in a draw"), Some(ChainEvent::Drawn { gameid: "g1".into() }));
+        assert_eq!(parse_event("Game g1 is over, player alice won"), Some(ChainEvent::Over { gameid: "g1".into() }));
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        assert_eq!(parse_event("Player alice, not your turn dummy!"), None);
+        assert_eq!(parse_event("Player alice fired at player bob at pos K3"), None);
+        assert_eq!(parse_event("Player alice scanned player bob at D5:B3"), None);
+    }
+
+    #[test]
+    fn targets_next_to_hits() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(target_shot(&grid_with(&[]), &mut rng), None);
+
+        let single = grid_with(&[(55, Cell::Hit), (45, Cell::Miss)]);
+        for _ in 0..20 {
+            let shot = target_shot(&single, &mut rng).unwrap();
+            assert!([54, 56, 65].contains(&shot), "shot {} is not next to the hit", shot);
+        }
+
+        // A line of hits is carried on at either end
+        let line = grid_with(&[(44, Cell::Hit), (45, Cell::Hit)]);
+        for _ in 0..20 {
+            let shot = target_shot(&line, &mut rng).unwrap();
+            assert!([43, 46].contains(&shot), "shot {} does not continue the line", shot);
+        }
+    }
+
+    #[test]
+    fn density_favours_cells_next_to_hits() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let grid = grid_with(&[(55, Cell::Hit)]);
+        for _ in 0..20 {
+            let shot = density_shot(&grid, &mut rng).unwrap();
+            assert!([45, 54, 56, 65].contains(&shot), "shot {} is not next to the hit", shot);
+        }
+
+        // Never a cell already tried, and nothing once every cell was
+        let mut tried = [Cell::Miss; 100];
+        tried[7] = Cell::Unknown;
+        assert_eq!(density_shot(&tried, &mut rng), Some(7));
+        assert_eq!(density_shot(&[Cell::Miss; 100], &mut rng), None);
+    }
+}
@@ -1,172 +1,485 @@
-// src/game_actions.rs
-
-use fleetcore::{BaseInputs, Command, FireInputs};
-use methods::{FIRE_ELF, JOIN_ELF, REPORT_ELF, WAVE_ELF, WIN_ELF};
-use risc0_zkvm::{default_prover, ExecutorEnv};
-
-use crate::{send_receipt, unmarshal_data, unmarshal_fire, unmarshal_report, FormData};
-
-pub async fn join_game(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
-        Ok(values) => values,
-        Err(err) => return err,
-    };
-
-    // Set up the zkVM environment
-    let receipt = {
-        let env = ExecutorEnv::builder()
-            .write(&BaseInputs {
-                gameid: gameid.clone(),
-                fleet: fleetid.clone(),
-                board: board.clone(),
-                random: random.clone(),
-            })
-            .unwrap()
-            .build()
-            .unwrap();
-        // Obtain the default prover
-        let prover = default_prover();
-
-        // Produce a receipt by proving the specified ELF binary
-        prover.prove(env, JOIN_ELF).unwrap().receipt
-    };
-
-    // Send the receipt to the blockchain server
-    send_receipt(Command::Join, receipt).await;
-
-    "OK".to_string()
-}
-
-pub async fn fire(idata: FormData) -> String {
-    // Unmarshal the input data
-    let (gameid, fleetid, board, random, targetfleet, x, y) = match unmarshal_fire(&idata) {
-        Ok(values) => values,
-        Err(err) => return err,
-    };
-
-    let pos = (y * 10 + x) as u8;
-
-    // Set up the zkVM environment
-    let receipt = {
-        let env = ExecutorEnv::builder()
-            .write(&FireInputs {
-                gameid: gameid.clone(),
-                fleet: fleetid.clone(),
-                board: board.clone(),
-                random: random.clone(),
-                target: targetfleet.clone(),
-                pos: pos.clone(),
-            })
-            .unwrap()
-            .build()
-            .unwrap();
-        // Obtain the default prover
-        let prover = default_prover();
-
-        // Produce a receipt by proving the specified ELF binary
-        prover.prove(env, FIRE_ELF).unwrap().receipt
-    };
-
-    // Send the receipt to the blockchain server
-    send_receipt(Command::Fire, receipt).await;
-
-    // Return success message
-    "OK".to_string()
-}
-
-pub async fn report(idata: FormData) -> String {
-    let (gameid, fleetid, board, random, report, x, y) = match unmarshal_report(&idata) {
-        Ok(values) => values,
-        Err(err) => return err,
-    };
-
-    let pos = (y * 10 + x) as u8;
-
-    // Set up the zkVM environment
-    let receipt = {
-        let env = ExecutorEnv::builder()
-            .write(&fleetcore::ReportInputs {
-                gameid: gameid.clone(),
-                fleet: fleetid.clone(),
-                board: board.clone(),
-                random: random.clone(),
-                report: report.clone(),
-                pos: pos.clone(),
-            })
-            .unwrap()
-            .build()
-            .unwrap();
-        // Obtain the default prover
-        let prover = default_prover();
-
-        // Produce a receipt by proving the specified ELF binary
-        prover.prove(env, REPORT_ELF).unwrap().receipt
-    };
-
-    // Send the receipt to the blockchain server
-    send_receipt(Command::Report, receipt).await;
-
-    "OK".to_string()
-}
-
-pub async fn wave(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
-        Ok(values) => values,
-        Err(err) => return err,
-    };
-
-    // Set up the zkVM environment
-    let receipt = {
-        let env = ExecutorEnv::builder()
-            .write(&BaseInputs {
-                gameid: gameid.clone(),
-                fleet: fleetid.clone(),
-                board: board.clone(),
-                random: random.clone(),
-            })
-            .unwrap()
-            .build()
-            .unwrap();
-        // Obtain the default prover
-        let prover = default_prover();
-
-        // Produce a receipt by proving the specified ELF binary
-        prover.prove(env, WAVE_ELF).unwrap().receipt
-    };
-
-    // Send the receipt to the blockchain server
-    send_receipt(Command::Wave, receipt).await;
-
-    "OK".to_string()
-}
-
-pub async fn win(idata: FormData) -> String {
-    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
-        Ok(values) => values,
-        Err(err) => return err,
-    };
-
-    // Set up the zkVM environment
-    let receipt = {
-        let env = ExecutorEnv::builder()
-            .write(&BaseInputs {
-                gameid: gameid.clone(),
-                fleet: fleetid.clone(),
-                board: board.clone(),
-                random: random.clone(),
-            })
-            .unwrap()
-            .build()
-            .unwrap();
-        // Obtain the default prover
-        let prover = default_prover();
-
-        // Produce a receipt by proving the specified ELF binary
-        prover.prove(env, WIN_ELF).unwrap().receipt
-    };
-
-    // Send the receipt to the blockchain server
-    send_receipt(Command::Win, receipt).await;
-
-    "OK".to_string()
-}
+// src/game_actions.rs
+
+use fleetcore::{BaseInputs, BatchReportInputs, Command, FireInputs, MoveInputs, OwnershipInputs, RevealData, ScanInputs, ScanReportInputs};
+use methods::{
+    BATCH_REPORT_ELF, BATCH_REPORT_ID, FIRE_ELF, JOIN_ELF, JOIN_ID, MOVE_SHIP_ELF, MOVE_SHIP_ID, OWNERSHIP_ELF, REPORT_ELF, REPORT_ID,
+    SCAN_ELF, SCAN_REPORT_ELF, WAVE_ELF, WIN_ELF,
+};
+use risc0_zkvm::{default_executor, default_prover, ExecutorEnv, ProverOpts, Receipt};
+use serde::Serialize;
+
+use crate::history::{fleet_mines, joined_board, latest_history, origin_board, record_history, record_joined, record_mines, record_origin};
+use crate::{
+    game_state, methods_compatible, send_receipt, submit_reveal, unmarshal_area, unmarshal_data, unmarshal_fire, unmarshal_mines, unmarshal_mode, unmarshal_move, unmarshal_report,
+    unmarshal_salvo, unmarshal_team, FormData, SEND_ERROR,
+};
+
+// Kind of receipt to produce, RECEIPT_KIND=succinct compresses the proof into a
+// single small receipt at the cost of extra proving time
+fn prover_opts() -> ProverOpts {
+    match std::env::var("RECEIPT_KIND").as_deref() {
+        Ok("succinct") => ProverOpts::succinct(),
+        _ => ProverOpts::composite(),
+    }
+}
+
+// Proves on a blocking thread, so bots sharing the runtime keep following their games
+async fn execute_and_prove<T: Serialize + Clone + Send + 'static>(
+    input: &T,
+    assumptions: &[Receipt],
+    elf: &'static [u8],
+) -> Result<Receipt, String> {
+    // A receipt the chain can't verify would only waste proving time
+    methods_compatible().await?;
+
+    let (input, assumptions) = (input.clone(), assumptions.to_vec());
+    tokio::task::spawn_blocking(move || prove_blocking(&input, &assumptions, elf))
+        .await
+        .map_err(|err| format!("Could not prove move: {}", err))?
+}
+
+// Runs the guest with the executor only, so an invalid board or report is
+// rejected with the guest's own message before any proving time is spent
+fn prove_blocking<T: Serialize>(input: &T, assumptions: &[Receipt], elf: &[u8]) -> Result<Receipt, String> {
+    let env = build_env(input, assumptions);
+    if let Err(err) = default_executor().execute(env, elf) {
+        return Err(guest_error(&err.to_string()));
+    }
+
+    // The environment is consumed by the executor, so build it again for the prover
+    let env = build_env(input, assumptions);
+    let start = std::time::Instant::now();
+    let receipt = default_prover()
+        .prove_with_opts(env, elf, &prover_opts())
+        .map(|info| info.receipt)
+        .map_err(|err| format!("Could not prove move: {}", err))?;
+    let size = serde_json::to_vec(&receipt).map_or(0, |bytes| bytes.len());
+    println!("Proved receipt of {} bytes in {} ms", size, start.elapsed().as_millis());
+    Ok(receipt)
+}
+
+fn build_env<'a, T: Serialize>(input: &T, assumptions: &[Receipt]) -> ExecutorEnv<'a> {
+    let mut builder = ExecutorEnv::builder();
+    builder.write(input).unwrap();
+    for receipt in assumptions {
+        builder.add_assumption(receipt.clone());
+    }
+    builder.build().unwrap()
+}
+
+// Turns "Guest panicked: panicked at src/bin/join.rs:1:2:\n<message>" into "<message>"
+fn guest_error(err: &str) -> String {
+    let message = match err.split_once("panicked at ") {
+        Some((_, location)) => location.split_once('\n').map_or(location, |(_, msg)| msg),
+        None => err,
+    };
+    format!("Invalid move: {}", message.trim())
+}
+
+// A receipt the chain refused is not part of the fleet's history, one that could not
+// be sent is, as it is saved to be submitted later
+fn extends_history(response: &str) -> bool {
+    response == "OK" || response == SEND_ERROR
+}
+
+pub async fn join_game(idata: FormData) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let mode = match unmarshal_mode(&idata) {
+        Ok(mode) => mode,
+        Err(err) => return err,
+    };
+    let team = match unmarshal_team(&idata) {
+        Ok(team) => team,
+        Err(err) => return err,
+    };
+    let mines = match unmarshal_mines(&idata) {
+        Ok(mines) => mines,
+        Err(err) => return err,
+    };
+
+    let input = BaseInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random: random.clone(),
+        team,
+        mines: mines.clone(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], JOIN_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    let response = send_receipt(Command::Join, receipt.clone(), mode).await;
+    if extends_history(&response) {
+        record_history(&gameid, &fleetid, Command::Join, &receipt);
+        record_origin(&gameid, &fleetid, &board);
+        record_joined(&gameid, &fleetid, &board);
+        record_mines(&gameid, &fleetid, &mines);
+    }
+    response
+}
+
+pub async fn fire(idata: FormData) -> String {
+    // Unmarshal the input data
+    let (gameid, fleetid, board, random, targetfleet, positions) = match unmarshal_fire(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    // A salvo is counted on the joined board. A single shot needs no more than the
+    // current one, so it can still be fired when this host did not see the join.
+    let origin = match origin_board(&gameid, &fleetid) {
+        Some(origin) => origin,
+        None if positions.len() == 1 => board.clone(),
+        None => return format!("The board fleet {} joined game {} with is not known to this host", fleetid, gameid),
+    };
+
+    let input = FireInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random: random.clone(),
+        target: targetfleet.clone(),
+        positions,
+        origin_board: origin,
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], FIRE_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    send_receipt(Command::Fire, receipt, None).await
+}
+
+pub async fn report(idata: FormData) -> String {
+    let (gameid, fleetid, board, random, report, x, y) = match unmarshal_report(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let pos = (y * 10 + x) as u8;
+
+    // The report proves it continues from the fleet's previous join, report or move receipt
+    let (history, previous) = match latest_history(&gameid, &fleetid) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let input = fleetcore::ReportInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random: random.clone(),
+        report: report.clone(),
+        pos: pos.clone(),
+        mines: fleet_mines(&gameid, &fleetid),
+        history,
+        join_id: JOIN_ID.into(),
+        report_id: REPORT_ID.into(),
+        move_id: MOVE_SHIP_ID.into(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[previous], REPORT_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    let response = send_receipt(Command::Report, receipt.clone(), None).await;
+    if extends_history(&response) {
+        record_history(&gameid, &fleetid, Command::Report, &receipt);
+    }
+    response
+}
+
+// Reports every shot of a salvo in one proof, each as it fell on the board
+pub async fn report_salvo(idata: FormData) -> String {
+    let (gameid, fleetid, board, random, positions) = match unmarshal_salvo(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    // The report proves it continues from the fleet's previous join or batch report receipt
+    let (history, previous) = match latest_history(&gameid, &fleetid) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let input = BatchReportInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board,
+        random,
+        positions,
+        history,
+        join_id: JOIN_ID.into(),
+        batch_report_id: BATCH_REPORT_ID.into(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[previous], BATCH_REPORT_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    let response = send_receipt(Command::BatchReport, receipt.clone(), None).await;
+    if extends_history(&response) {
+        record_history(&gameid, &fleetid, Command::BatchReport, &receipt);
+    }
+    response
+}
+
+pub async fn wave(idata: FormData) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let input = BaseInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random: random.clone(),
+        team: None,
+        mines: Vec::new(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], WAVE_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    send_receipt(Command::Wave, receipt, None).await
+}
+
+pub async fn win(idata: FormData) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let input = BaseInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random: random.clone(),
+        team: None,
+        mines: Vec::new(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], WIN_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    send_receipt(Command::Win, receipt, None).await
+}
+
+// Gives up the game, the fleet is eliminated at once
+pub async fn resign(idata: FormData) -> String {
+    prove_ownership(idata, Command::Resign).await
+}
+
+// Offers the other fleets afloat a draw, the game ends once all of them accept it
+pub async fn offer_draw(idata: FormData) -> String {
+    prove_ownership(idata, Command::OfferDraw).await
+}
+
+pub async fn accept_draw(idata: FormData) -> String {
+    prove_ownership(idata, Command::AcceptDraw).await
+}
+
+// Sends a command that only proves the fleet owns its board
+async fn prove_ownership(idata: FormData, cmd: Command) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    // A draw receipt names the offer it makes or accepts, the chain numbers them
+    let offers = match game_state(&gameid).await {
+        Ok(game) => game["draw_offers"].as_u64().unwrap_or(0) as u32,
+        Err(err) => return err,
+    };
+    let input = OwnershipInputs {
+        gameid,
+        fleet: fleetid,
+        board,
+        random,
+        action: cmd,
+        offer: if cmd == Command::OfferDraw { offers + 1 } else { offers },
+    };
+
+    let receipt = match execute_and_prove(&input, &[], OWNERSHIP_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+    send_receipt(cmd, receipt, None).await
+}
+
+// Shows the board and mines the fleet joined with once the game is over, the chain
+// checks them against the join and every report
+pub async fn reveal(idata: FormData) -> String {
+    let (gameid, fleetid, _, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let Some(board) = joined_board(&gameid, &fleetid) else {
+        return format!("Fleet {} did not join game {} from this host", fleetid, gameid);
+    };
+
+    let data = RevealData {
+        mines: fleet_mines(&gameid, &fleetid),
+        gameid,
+        fleet: fleetid,
+        board,
+        random,
+    };
+    submit_reveal(&data).await
+}
+
+pub async fn scan(idata: FormData) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let area = match unmarshal_area(&idata, &idata.x, &idata.y) {
+        Ok(area) => area,
+        Err(err) => return err,
+    };
+    let targetfleet = match idata.targetfleet.clone() {
+        Some(target) => target,
+        None => return "You must provide a Target Fleet ID".to_string(),
+    };
+
+    let input = ScanInputs {
+        gameid,
+        fleet: fleetid,
+        board,
+        random,
+        target: targetfleet,
+        area,
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], SCAN_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    send_receipt(Command::Scan, receipt, None).await
+}
+
+// Answers the scan of the area at rx and ry with the number of ship cells in it
+pub async fn answer_scan(idata: FormData) -> String {
+    let (gameid, fleetid, board, random) = match unmarshal_data(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let area = match unmarshal_area(&idata, &idata.rx, &idata.ry) {
+        Ok(area) => area,
+        Err(err) => return err,
+    };
+
+    let input = ScanReportInputs {
+        gameid,
+        fleet: fleetid,
+        board,
+        random,
+        area,
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[], SCAN_REPORT_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    send_receipt(Command::ScanReport, receipt, None).await
+}
+
+// Board after the move, or why the ship can't move. Hit cells are the cells of the
+// layout this host recorded that are no longer on the board.
+pub fn moved_board(idata: &FormData) -> Result<Vec<u8>, String> {
+    let (gameid, fleetid, board, _, ship, direction) = unmarshal_move(idata)?;
+    let hits = fleet_hits(&gameid, &fleetid, &board)?;
+    fleetcore::move_ship(&board, &hits, &fleet_mines(&gameid, &fleetid), ship, direction)
+}
+
+fn fleet_hits(gameid: &str, fleetid: &str, board: &[u8]) -> Result<Vec<u8>, String> {
+    let Some(layout) = origin_board(gameid, fleetid) else {
+        return Err(format!("The board fleet {} joined game {} with is not known to this host", fleetid, gameid));
+    };
+    let mut hits: Vec<u8> = layout.into_iter().filter(|pos| !board.contains(pos)).collect();
+    hits.sort();
+    Ok(hits)
+}
+
+// Moves an undamaged ship by one cell instead of firing, in a Movable game
+pub async fn move_ship(idata: FormData) -> String {
+    let (gameid, fleetid, board, random, ship, direction) = match unmarshal_move(&idata) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+    let hits = match fleet_hits(&gameid, &fleetid, &board) {
+        Ok(hits) => hits,
+        Err(err) => return err,
+    };
+    let mines = fleet_mines(&gameid, &fleetid);
+
+    // The move proves it continues from the fleet's previous join, report or move receipt
+    let (history, previous) = match latest_history(&gameid, &fleetid) {
+        Ok(values) => values,
+        Err(err) => return err,
+    };
+
+    let input = MoveInputs {
+        gameid: gameid.clone(),
+        fleet: fleetid.clone(),
+        board: board.clone(),
+        random,
+        hits: hits.clone(),
+        ship,
+        direction,
+        mines: mines.clone(),
+        history,
+        join_id: JOIN_ID.into(),
+        report_id: REPORT_ID.into(),
+        move_id: MOVE_SHIP_ID.into(),
+    };
+
+    // Check the move with the executor first, then produce a receipt for it
+    let receipt = match execute_and_prove(&input, &[previous], MOVE_SHIP_ELF).await {
+        Ok(receipt) => receipt,
+        Err(err) => return err,
+    };
+
+    // Send the receipt to the blockchain server
+    let response = send_receipt(Command::Move, receipt.clone(), None).await;
+    if extends_history(&response) {
+        record_history(&gameid, &fleetid, Command::Move, &receipt);
+        // The fleet's ships now lie on the moved board and the cells hit before
+        if let Ok(next) = fleetcore::move_ship(&board, &hits, &mines, ship, direction) {
+            record_origin(&gameid, &fleetid, &[next, hits].concat());
+        }
+    }
+    response
+}
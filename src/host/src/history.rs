@@ -0,0 +1,138 @@
+// src/history.rs
+
+use fleetcore::{BaseJournal, BatchReportJournal, BoardHistory, Command, CommunicationData, MoveJournal, ReportJournal};
+use risc0_zkvm::Receipt;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use crate::{list_receipts, load_receipt, load_secret, save_secret, was_accepted};
+
+// Game and fleet ids
+type FleetKey = (String, String);
+
+// Latest join, report or move receipt of every (game, fleet) proven by this host.
+// The next report takes it as an assumption to extend the fleet's board history.
+static LATEST: LazyLock<Mutex<HashMap<FleetKey, CommunicationData>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Boards every fleet joined with, a fire proves its salvo size on it. After a move
+// it is the fleet's layout with its hit cells, which are the cells missing from the board.
+// Only the commitment is public, so the board itself is not found in the receipts,
+// it is kept under RECEIPTS_DIR instead.
+static ORIGINS: LazyLock<Mutex<HashMap<FleetKey, Vec<u8>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn record_history(gameid: &str, fleet: &str, cmd: Command, receipt: &Receipt) {
+    LATEST.lock().unwrap().insert(
+        (gameid.to_string(), fleet.to_string()),
+        CommunicationData {
+            cmd,
+            receipt: receipt.clone(),
+            mode: None,
+        },
+    );
+}
+
+// Keeps the cells in memory and on disk, where a restarted host finds them again
+fn remember(cache: &Mutex<HashMap<FleetKey, Vec<u8>>>, kind: &str, gameid: &str, fleet: &str, cells: &[u8]) {
+    cache
+        .lock()
+        .unwrap()
+        .insert((gameid.to_string(), fleet.to_string()), cells.to_vec());
+    if let Err(err) = save_secret(kind, gameid, fleet, cells) {
+        eprintln!("{}", err);
+    }
+}
+
+// Cells from memory, or from disk when the host was restarted since they were recorded
+fn recall(cache: &Mutex<HashMap<FleetKey, Vec<u8>>>, kind: &str, gameid: &str, fleet: &str) -> Option<Vec<u8>> {
+    let key = (gameid.to_string(), fleet.to_string());
+    if let Some(cells) = cache.lock().unwrap().get(&key) {
+        return Some(cells.clone());
+    }
+    let cells = load_secret(kind, gameid, fleet)?;
+    cache.lock().unwrap().insert(key, cells.clone());
+    Some(cells)
+}
+
+pub fn record_origin(gameid: &str, fleet: &str, board: &[u8]) {
+    remember(&ORIGINS, "origin", gameid, fleet, board);
+}
+
+// Boards as every fleet joined with them, in the order committed at join. Unlike the
+// origin they stay put when a ship moves, a reveal opens the join commitment with them.
+static JOINED: LazyLock<Mutex<HashMap<FleetKey, Vec<u8>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn record_joined(gameid: &str, fleet: &str, board: &[u8]) {
+    remember(&JOINED, "joined", gameid, fleet, board);
+}
+
+pub fn joined_board(gameid: &str, fleet: &str) -> Option<Vec<u8>> {
+    recall(&JOINED, "joined", gameid, fleet)
+}
+
+// Mines every fleet placed at join, its reports prove shots on them
+static MINES: LazyLock<Mutex<HashMap<FleetKey, Vec<u8>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn record_mines(gameid: &str, fleet: &str, mines: &[u8]) {
+    remember(&MINES, "mines", gameid, fleet, mines);
+}
+
+// Empty when the fleet placed no mines
+pub fn fleet_mines(gameid: &str, fleet: &str) -> Vec<u8> {
+    recall(&MINES, "mines", gameid, fleet).unwrap_or_default()
+}
+
+pub fn origin_board(gameid: &str, fleet: &str) -> Option<Vec<u8>> {
+    recall(&ORIGINS, "origin", gameid, fleet)
+}
+
+fn decode_history(data: &CommunicationData) -> Option<BoardHistory> {
+    match data.cmd {
+        Command::Join => data.receipt.journal.decode::<BaseJournal>().ok().map(BoardHistory::Joined),
+        Command::Report => data.receipt.journal.decode::<ReportJournal>().ok().map(BoardHistory::Reported),
+        Command::BatchReport => data
+            .receipt
+            .journal
+            .decode::<BatchReportJournal>()
+            .ok()
+            .map(BoardHistory::BatchReported),
+        Command::Move => data.receipt.journal.decode::<MoveJournal>().ok().map(BoardHistory::Moved),
+        _ => None,
+    }
+}
+
+fn belongs_to(history: &BoardHistory, gameid: &str, fleet: &str) -> bool {
+    match history {
+        BoardHistory::Joined(join) => join.gameid == gameid && join.fleet == fleet,
+        BoardHistory::Reported(report) => report.gameid == gameid && report.fleet == fleet,
+        BoardHistory::BatchReported(report) => report.gameid == gameid && report.fleet == fleet,
+        BoardHistory::Moved(moved) => moved.gameid == gameid && moved.fleet == fleet,
+    }
+}
+
+// Journal and receipt the next report of the fleet must build on. Falls back to
+// the saved receipts the chain accepted, so a restarted host can carry on with a
+// running game.
+pub fn latest_history(gameid: &str, fleet: &str) -> Result<(BoardHistory, Receipt), String> {
+    let key = (gameid.to_string(), fleet.to_string());
+    if let Some(data) = LATEST.lock().unwrap().get(&key) {
+        if let Some(history) = decode_history(data) {
+            return Ok((history, data.receipt.clone()));
+        }
+    }
+
+    for name in list_receipts().iter().rev().filter(|name| was_accepted(name)) {
+        let Ok(data) = load_receipt(name) else {
+            continue;
+        };
+        if let Some(history) = decode_history(&data) {
+            if belongs_to(&history, gameid, fleet) {
+                return Ok((history, data.receipt));
+            }
+        }
+    }
+    Err(format!("No join receipt found for fleet {} in game {}", fleet, gameid))
+}
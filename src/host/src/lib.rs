@@ -1,168 +1,427 @@
-// Remove the following 3 lines to enable compiler checkings
-#![allow(unused_imports)]
-#![allow(unused_variables)]
-#![allow(dead_code)]
-
-use percent_encoding;
-use serde::{Deserialize,Serialize};
-mod game_actions;
-
-use fleetcore::{Command, CommunicationData};
-use std::error::Error;
-use risc0_zkvm::Receipt;
-use risc0_zkvm::{default_prover, ExecutorEnv};
-
-pub use game_actions::{join_game, fire, report, wave, win};
-
-async fn send_receipt(action: Command, receipt: Receipt) -> String {
-    let client = reqwest::Client::new();
-    let res = client
-    .post("http://chain0:3001/chain")
-    .json(&CommunicationData {
-        cmd: action,
-        receipt,
-    })
-    .send()
-    .await;
-
-    match res {
-        Ok(response) => response.text().await.unwrap(),
-        Err(_) => "Error sending receipt".to_string(),
-    }
-}
-
-
-#[derive(Deserialize)]
-pub struct FormData {
-    pub button: String,
-    pub gameid: Option<String>,
-    pub fleetid: Option<String>,
-    pub targetfleet: Option<String>,
-    pub x: Option<String>,
-    pub y: Option<String>,
-    pub rx: Option<String>,
-    pub ry: Option<String>,
-    pub report: Option<String>,
-    pub board: Option<String>,
-    pub shots: Option<String>,
-    pub random: Option<String>,
-}
-
-pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Vec<u8>, String), String> {
-    let gameid = idata
-    .gameid
-    .clone()
-    .ok_or_else(|| "You must provide a Game ID".to_string())
-    .and_then(|id| {
-        if id.is_empty() {
-            Err("Game ID cannot be an empty string".to_string())
-        } else {
-            Ok(id)
-        }
-    })?;
-    let fleetid = idata
-    .fleetid
-    .clone()
-    .ok_or_else(|| "You must provide a Fleet ID".to_string())
-    .and_then(|id| {
-        if id.is_empty() {
-            Err("Fleet ID cannot be an empty string".to_string())
-        } else {
-            Ok(id)
-        }
-    })?;
-    let random: String = idata
-    .random
-    .clone()
-    .ok_or_else(|| "You must provide a Random Seed".to_string())?;
-
-    let board = idata
-    .board
-    .as_ref()
-    .ok_or_else(|| "You must provide a Board Placement".to_string())
-    .and_then(|id| {
-        percent_encoding::percent_decode_str(id)
-        .decode_utf8()
-        .map_err(|_| "Invalid Board Placement".to_string())
-        .map(|decoded| {
-            decoded
-            .split(',')
-            .map(|s| {
-                s.parse::<u8>()
-                .map_err(|_| "Invalid number in Board Placement".to_string())
-            })
-            .collect::<Result<Vec<u8>, String>>()
-        })
-    })??;
-
-    Ok((gameid, fleetid, board, random))
-}
-
-fn get_coordinates(x: &Option<String>, y: &Option<String>) -> Result<(u8, u8), String> {
-    let x: u8 = x
-    .as_ref()
-    .ok_or_else(|| "You must provide an X coordinate".to_string())
-    .and_then(|id| {
-        if let Some(first_char) = id.chars().next() {
-            if ('A'..='J').contains(&first_char) {
-                Ok(first_char as u8 - b'A')
-            } else {
-                Err("X coordinate must be between A and J".to_string())
-            }
-        } else {
-            Err("Invalid X coordinate".to_string())
-        }
-    })?;
-
-    let y: u8 = y
-    .as_ref()
-    .ok_or_else(|| "You must provide a Y coordinate".to_string())
-    .and_then(|id| {
-        if let Some(first_char) = id.chars().next() {
-            if ('0'..='9').contains(&first_char) {
-                Ok(first_char as u8 - b'0')
-            } else {
-                Err("Y coordinate must be between 0 and 9".to_string())
-            }
-        } else {
-            Err("Invalid Y coordinate".to_string())
-        }
-    })?;
-
-    Ok((x, y))
-}
-
-pub fn unmarshal_fire(
-    idata: &FormData,
-) -> Result<(String, String, Vec<u8>, String, String, u8, u8), String> {
-    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
-    let (x, y) = get_coordinates(&idata.x, &idata.y)?;
-    let targetfleet = idata
-    .targetfleet
-    .clone()
-    .ok_or_else(|| "You must provide a Target Fleet ID".to_string())?;
-
-    Ok((gameid, fleetid, board, random, targetfleet, x, y))
-}
-
-pub fn unmarshal_report(
-    idata: &FormData,
-) -> Result<(String, String, Vec<u8>, String, String, u8, u8), String> {
-    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
-    let (x, y) = get_coordinates(&idata.rx, &idata.ry)?;
-    let report = idata
-    .report
-    .clone()
-    .ok_or_else(|| "You must provide a Report value".to_string())
-    .and_then(|r| {
-        if r == "Hit" || r == "Miss" {
-            Ok(r)
-        } else {
-            Err("Report must be either 'Hit' or 'Miss'".to_string())
-        }
-    })?;
-
-    Ok((gameid, fleetid, board, random, report, x, y))
-}
-
-
+// Remove the following 3 lines to enable compiler checkings
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use percent_encoding;
+use serde::{Deserialize,Serialize};
+mod bot;
+mod game_actions;
+mod history;
+mod receipts;
+mod session;
+
+use fleetcore::{Command, CommunicationData, Direction, GameMode, MethodSet, RevealData, ScanArea, VersionInfo, PROTOCOL_VERSION, SCAN_SIZE};
+use methods::{BATCH_REPORT_ID, FIRE_ID, JOIN_ID, MOVE_SHIP_ID, OWNERSHIP_ID, REPORT_ID, SCAN_ID, SCAN_REPORT_ID, WAVE_ID, WIN_ID};
+use std::sync::Mutex;
+use std::error::Error;
+use risc0_zkvm::Receipt;
+use risc0_zkvm::{default_prover, ExecutorEnv};
+
+pub use bot::{run_bot, BotConfig, Difficulty};
+pub use game_actions::{join_game, fire, report, report_salvo, wave, win, resign, offer_draw, accept_draw, reveal, scan, answer_scan, move_ship, moved_board};
+pub use session::{follow_game, shot_map_html};
+pub use receipts::{list_receipts, load_receipt, load_receipt_file, read_receipt, save_receipt};
+use receipts::{load_secret, mark_accepted, save_secret, was_accepted};
+
+async fn send_receipt(action: Command, receipt: Receipt, mode: Option<GameMode>) -> String {
+    let data = CommunicationData {
+        cmd: action,
+        receipt,
+        mode,
+    };
+
+    // Keep a copy so the proof is not lost if the chain can't be reached
+    let saved = save_receipt(&data);
+    if let Err(err) = &saved {
+        eprintln!("{}", err);
+    }
+
+    let response = submit_data(&data).await;
+    if let (Ok(name), "OK") = (&saved, response.as_str()) {
+        accepted(name);
+    }
+    response
+}
+
+// Notes next to the saved receipt that the chain accepted it, a restarted host only
+// builds on accepted receipts
+fn accepted(name: &str) {
+    if let Err(err) = mark_accepted(name) {
+        eprintln!("{}", err);
+    }
+}
+
+// Address of the blockchain server, CHAIN_URL overrides the docker-compose default
+pub fn chain_url() -> String {
+    std::env::var("CHAIN_URL").unwrap_or_else(|_| "http://chain0:3001".to_string())
+}
+
+// State of the game as the chain's /games/:id endpoint shows it
+pub(crate) async fn game_state(gameid: &str) -> Result<serde_json::Value, String> {
+    reqwest::get(format!("{}/games/{}", chain_url(), gameid))
+        .await
+        .map_err(|err| format!("Could not get the game: {}", err))?
+        .json()
+        .await
+        .map_err(|err| format!("Invalid game state: {}", err))
+}
+
+// Image IDs of the guests this host proves with
+pub fn local_methods() -> MethodSet {
+    MethodSet {
+        protocol: PROTOCOL_VERSION,
+        join: JOIN_ID.into(),
+        fire: FIRE_ID.into(),
+        report: REPORT_ID.into(),
+        batch_report: BATCH_REPORT_ID.into(),
+        wave: WAVE_ID.into(),
+        win: WIN_ID.into(),
+        scan: SCAN_ID.into(),
+        scan_report: SCAN_REPORT_ID.into(),
+        move_ship: MOVE_SHIP_ID.into(),
+        ownership: OWNERSHIP_ID.into(),
+    }
+}
+
+// Outcome of the last check against the chain that got an answer, proving is refused
+// while it is an error. None until the chain could be reached.
+static METHODS_CHECK: Mutex<Option<Result<(), String>>> = Mutex::new(None);
+
+// Asks the chain which methods it accepts and remembers whether ours are among them.
+// An unreachable chain is not an error, receipts can still be proven and submitted
+// later, but the methods stay unchecked until the chain answers.
+pub async fn check_methods() -> Result<(), String> {
+    let local = local_methods();
+    let result = match reqwest::get(format!("{}/version", chain_url())).await {
+        Ok(response) => match response.json::<VersionInfo>().await {
+            Ok(info) if info.accepted.contains(&local) => Ok(()),
+            Ok(info) => Err(format!(
+                "The chain (protocol {}) does not accept this host's methods (protocol {}), rebuild with matching guests",
+                info.protocol, local.protocol
+            )),
+            Err(err) => Err(format!("Invalid version information from the chain: {}", err)),
+        },
+        Err(err) => {
+            eprintln!("Could not check methods with the chain: {}", err);
+            return Ok(());
+        }
+    };
+    *METHODS_CHECK.lock().unwrap() = Some(result.clone());
+    result
+}
+
+// Checks the methods again while the chain never answered
+pub(crate) async fn methods_compatible() -> Result<(), String> {
+    let checked = METHODS_CHECK.lock().unwrap().clone();
+    match checked {
+        Some(result) => result,
+        None => check_methods().await,
+    }
+}
+
+// Answer when the chain can't be reached, the receipt is saved and can be submitted later
+pub(crate) const SEND_ERROR: &str = "Error sending receipt";
+
+// Sends an already proven command to the blockchain server, returns "OK" or why the chain refused it
+pub async fn submit_data(data: &CommunicationData) -> String {
+    let client = reqwest::Client::new();
+    let res = client
+    .post(format!("{}/chain", chain_url()))
+    .json(data)
+    .send()
+    .await;
+
+    match res {
+        Ok(response) => response.text().await.unwrap(),
+        Err(_) => SEND_ERROR.to_string(),
+    }
+}
+
+// Sends a fleet's board to the blockchain server once its game is over
+pub(crate) async fn submit_reveal(data: &RevealData) -> String {
+    let res = reqwest::Client::new()
+        .post(format!("{}/reveal", chain_url()))
+        .json(data)
+        .send()
+        .await;
+
+    match res {
+        Ok(response) => response.text().await.unwrap(),
+        Err(_) => "Error sending reveal".to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    pub button: String,
+    pub gameid: Option<String>,
+    pub fleetid: Option<String>,
+    pub targetfleet: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+    pub rx: Option<String>,
+    pub ry: Option<String>,
+    pub report: Option<String>,
+    pub board: Option<String>,
+    pub shots: Option<String>,
+    pub random: Option<String>,
+    pub seed: Option<String>,
+    // Mode of the game a join creates, Classic or Salvo
+    pub mode: Option<String>,
+    // Positions of a salvo, e.g. "B3,C4"
+    pub salvo: Option<String>,
+    // Team declared at join, for a game played in teams
+    pub team: Option<String>,
+    // Side of the area a radar scan covers, the chain's default size when empty
+    pub size: Option<String>,
+    // Way a ship moves in a Movable game: Up, Down, Left or Right
+    pub direction: Option<String>,
+    // Mines placed at join in a game played with them, e.g. "B3,C4,D5"
+    pub mines: Option<String>,
+}
+
+// Generates a valid fleet placement in the same format the page sends in the board field
+pub fn random_placement(seed: Option<u64>) -> String {
+    let board = match seed {
+        Some(seed) => fleetcore::seeded_board(seed),
+        None => fleetcore::random_board(&mut rand::thread_rng()),
+    };
+    board
+        .iter()
+        .map(|pos| pos.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// Submits a receipt saved earlier, possibly proven on another machine
+pub async fn submit_saved_receipt(name: &str) -> String {
+    match load_receipt(name) {
+        Ok(data) => {
+            let response = submit_data(&data).await;
+            if response == "OK" {
+                accepted(name);
+            }
+            response
+        }
+        Err(err) => err,
+    }
+}
+
+pub fn unmarshal_seed(idata: &FormData) -> Result<Option<u64>, String> {
+    match idata.seed.as_deref() {
+        None | Some("") => Ok(None),
+        Some(seed) => seed
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| "Seed must be a non-negative integer".to_string()),
+    }
+}
+
+pub fn unmarshal_data(idata: &FormData) -> Result<(String, String, Vec<u8>, String), String> {
+    let gameid = idata
+    .gameid
+    .clone()
+    .ok_or_else(|| "You must provide a Game ID".to_string())
+    .and_then(|id| {
+        if id.is_empty() {
+            Err("Game ID cannot be an empty string".to_string())
+        } else {
+            Ok(id)
+        }
+    })?;
+    let fleetid = idata
+    .fleetid
+    .clone()
+    .ok_or_else(|| "You must provide a Fleet ID".to_string())
+    .and_then(|id| {
+        if id.is_empty() {
+            Err("Fleet ID cannot be an empty string".to_string())
+        } else {
+            Ok(id)
+        }
+    })?;
+    let random: String = idata
+    .random
+    .clone()
+    .ok_or_else(|| "You must provide a Random Seed".to_string())?;
+
+    let board = idata
+    .board
+    .as_ref()
+    .ok_or_else(|| "You must provide a Board Placement".to_string())
+    .and_then(|id| {
+        percent_encoding::percent_decode_str(id)
+        .decode_utf8()
+        .map_err(|_| "Invalid Board Placement".to_string())
+        .map(|decoded| {
+            decoded
+            .split(',')
+            .map(|s| {
+                s.parse::<u8>()
+                .map_err(|_| "Invalid number in Board Placement".to_string())
+            })
+            .collect::<Result<Vec<u8>, String>>()
+        })
+    })??;
+
+    Ok((gameid, fleetid, board, random))
+}
+
+fn get_coordinates(x: &Option<String>, y: &Option<String>) -> Result<(u8, u8), String> {
+    let x: u8 = x
+    .as_ref()
+    .ok_or_else(|| "You must provide an X coordinate".to_string())
+    .and_then(|id| {
+        if let Some(first_char) = id.chars().next() {
+            if ('A'..='J').contains(&first_char) {
+                Ok(first_char as u8 - b'A')
+            } else {
+                Err("X coordinate must be between A and J".to_string())
+            }
+        } else {
+            Err("Invalid X coordinate".to_string())
+        }
+    })?;
+
+    let y: u8 = y
+    .as_ref()
+    .ok_or_else(|| "You must provide a Y coordinate".to_string())
+    .and_then(|id| {
+        if let Some(first_char) = id.chars().next() {
+            if ('0'..='9').contains(&first_char) {
+                Ok(first_char as u8 - b'0')
+            } else {
+                Err("Y coordinate must be between 0 and 9".to_string())
+            }
+        } else {
+            Err("Invalid Y coordinate".to_string())
+        }
+    })?;
+
+    Ok((x, y))
+}
+
+// A salvo when positions are given, otherwise the single shot at x and y
+pub fn unmarshal_fire(
+    idata: &FormData,
+) -> Result<(String, String, Vec<u8>, String, String, Vec<u8>), String> {
+    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
+    let positions = match idata.salvo.as_deref() {
+        Some(salvo) if !salvo.trim().is_empty() => parse_positions(salvo)?,
+        _ => {
+            let (x, y) = get_coordinates(&idata.x, &idata.y)?;
+            vec![y * 10 + x]
+        }
+    };
+    let targetfleet = idata
+    .targetfleet
+    .clone()
+    .ok_or_else(|| "You must provide a Target Fleet ID".to_string())?;
+
+    Ok((gameid, fleetid, board, random, targetfleet, positions))
+}
+
+pub fn unmarshal_salvo(idata: &FormData) -> Result<(String, String, Vec<u8>, String, Vec<u8>), String> {
+    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
+    let positions = match idata.salvo.as_deref() {
+        Some(salvo) if !salvo.trim().is_empty() => parse_positions(salvo)?,
+        _ => return Err("You must provide the positions of the salvo".to_string()),
+    };
+    Ok((gameid, fleetid, board, random, positions))
+}
+
+pub fn unmarshal_mode(idata: &FormData) -> Result<Option<GameMode>, String> {
+    match idata.mode.as_deref() {
+        None | Some("") => Ok(None),
+        Some("Classic") => Ok(Some(GameMode::Classic)),
+        Some("Salvo") => Ok(Some(GameMode::Salvo)),
+        Some("Movable") => Ok(Some(GameMode::Movable)),
+        Some(mode) => Err(format!("Unknown game mode {}", mode)),
+    }
+}
+
+pub fn unmarshal_mines(idata: &FormData) -> Result<Vec<u8>, String> {
+    match idata.mines.as_deref().map(str::trim) {
+        None | Some("") => Ok(Vec::new()),
+        Some(mines) => parse_positions(mines),
+    }
+}
+
+pub fn unmarshal_team(idata: &FormData) -> Result<Option<String>, String> {
+    match idata.team.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(team) if team.contains(char::is_whitespace) => Err("Team names can't contain spaces".to_string()),
+        Some(team) => Ok(Some(team.to_string())),
+    }
+}
+
+// Area of a radar scan from its top left cell, given with x and y for a scan and
+// with rx and ry for its answer
+pub fn unmarshal_area(idata: &FormData, x: &Option<String>, y: &Option<String>) -> Result<ScanArea, String> {
+    let (x, y) = get_coordinates(x, y)?;
+    let size = match idata.size.as_deref().map(str::trim) {
+        None | Some("") => SCAN_SIZE,
+        Some(size) => size
+            .parse::<u8>()
+            .map_err(|_| "Scan size must be a positive integer".to_string())?,
+    };
+    let area = ScanArea { pos: y * 10 + x, size };
+    if !area.fits() {
+        return Err(format!("A {}x{} scan from {}{} does not lie on the board", size, size, (x + b'A') as char, y));
+    }
+    Ok(area)
+}
+
+// Cell of the ship to move, at x and y, and the way it moves
+pub fn unmarshal_move(idata: &FormData) -> Result<(String, String, Vec<u8>, String, u8, Direction), String> {
+    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
+    let (x, y) = get_coordinates(&idata.x, &idata.y)?;
+    let direction = match idata.direction.as_deref() {
+        Some("Up") => Direction::Up,
+        Some("Down") => Direction::Down,
+        Some("Left") => Direction::Left,
+        Some("Right") => Direction::Right,
+        _ => return Err("Direction must be Up, Down, Left or Right".to_string()),
+    };
+    Ok((gameid, fleetid, board, random, y * 10 + x, direction))
+}
+
+// Parses "B3,C4" into board positions
+fn parse_positions(salvo: &str) -> Result<Vec<u8>, String> {
+    salvo
+        .split(',')
+        .map(|xy| {
+            let xy = xy.trim();
+            let mut chars = xy.chars();
+            let (x, y) = (chars.next().map(|c| c.to_string()), chars.next().map(|c| c.to_string()));
+            if chars.next().is_some() {
+                return Err(format!("Invalid position {}", xy));
+            }
+            let (x, y) = get_coordinates(&x, &y)?;
+            Ok(y * 10 + x)
+        })
+        .collect()
+}
+
+pub fn unmarshal_report(
+    idata: &FormData,
+) -> Result<(String, String, Vec<u8>, String, String, u8, u8), String> {
+    let (gameid, fleetid, board, random) = unmarshal_data(idata)?;
+    let (x, y) = get_coordinates(&idata.rx, &idata.ry)?;
+    let report = idata
+    .report
+    .clone()
+    .ok_or_else(|| "You must provide a Report value".to_string())
+    .and_then(|r| {
+        if r == "Hit" || r == "Miss" || r == "Mine" {
+            Ok(r)
+        } else {
+            Err("Report must be either 'Hit', 'Miss' or 'Mine'".to_string())
+        }
+    })?;
+
+    Ok((gameid, fleetid, board, random, report, x, y))
+}
+
+
@@ -1,128 +1,300 @@
-// Remove the following 3 lines to enable compiler checkings
-#![allow(unused_imports)]
-#![allow(unused_variables)]
-#![allow(dead_code)]
-
-use axum::{
-    extract::Form,
-    response::Html,
-    routing::{get, post},
-    Router,
-};
-use tokio::signal;
-use nanoid::nanoid;
-
-use host::{fire, join_game, report, wave, win, FormData};
-use std::net::SocketAddr;
-
-async fn index() -> Html<String> {
-    render_html(None, None, None, None, None, None)
-}
-
-fn process_input_data(input_data: FormData) -> FormData {
-    match &input_data.random {
-        Some(random) if !random.is_empty() => input_data,
-        _ => FormData {
-            random: Some(nanoid!(12)),
-            ..input_data
-        },
-    }
-}
-
-async fn submit(Form(input_data): Form<FormData>) -> Html<String> {
-    let gameid = input_data.gameid.clone();
-    let fleetid = input_data.fleetid.clone();
-    let data = process_input_data(input_data);
-    let random = data.random.clone();
-    let board = data.board.clone();
-    let shots = data.shots.clone();
-    let response_text = match data.button.as_str() {
-        "Join" => join_game(data).await,
-        "Fire" => fire(data).await,
-        "Report" => report(data).await,
-        "Wave" => wave(data).await,
-        "Win" => win(data).await,
-        _ => "Unknown button pressed".to_string(),
-    };
-    render_html(gameid, fleetid, random, board, shots, Some(response_text))
-}
-
-fn render_html(
-    gameid: Option<String>,
-    fleetid: Option<String>,
-    random: Option<String>,
-    board: Option<String>,
-    shots: Option<String>,
-    response: Option<String>,
-) -> Html<String> {
-    let fleetid = fleetid.unwrap_or("".to_string());
-    let gameid = gameid.unwrap_or("".to_string());
-    let response_html = if let Some(response) = response {
-        if response == "OK" {
-            if gameid != "" {
-                format!("Playing Game: <b>{}</b> with fleet's ID: <b>{}</b> ", gameid, fleetid)
-            } else {
-                "Not in game".to_string()
-            }
-        } else {
-            format!("<p style='color:red'>{}</p>", response)
-        }
-    } else {
-        "".to_string()
-    };
-    let random = random.unwrap_or("".to_string());
-
-    let board = board.unwrap_or("".to_string());
-    let shots = shots.unwrap_or("".to_string());
-
-    let path = "host/src/page.html";
-    let html = std::fs::read_to_string(path).unwrap();
-    let html = html.replace("{response_html}", &response_html);
-    let html = html.replace("{gameid}", &gameid);
-    let html = html.replace("{fleetid}", &fleetid);
-    let html = html.replace("{random}", &random);
-    let html = html.replace("{board}", &board);
-    let html = html.replace("{shots}", &shots);
-
-    Html(html)
-}
-
-#[tokio::main]
-async fn main() {
-    let app = Router::new()
-        .route("/", get(index))
-        .route("/submit", post(submit));
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    println!("Listening on {}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
-}
-
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("failed to install Ctrl+C handler");
-    };
-
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("failed to install signal handler")
-            .recv()
-            .await;
-    };
-
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        _ = ctrl_c => {},
-        _ = terminate => {},
-    }
-}
+// Remove the following 3 lines to enable compiler checkings
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+#![allow(dead_code)]
+
+use axum::{
+    extract::{Form, Path},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::{get, post},
+    Router,
+};
+use tokio::signal;
+use nanoid::nanoid;
+
+use host::{
+    check_methods, fire, join_game, list_receipts, load_receipt_file, random_placement,
+    read_receipt, report, report_salvo, run_bot, follow_game, shot_map_html, scan, answer_scan, move_ship, moved_board, submit_data, submit_saved_receipt, unmarshal_seed,
+    wave, win, resign, offer_draw, accept_draw, reveal,
+    BotConfig, Difficulty, FormData,
+};
+use fleetcore::GameMode;
+use std::net::SocketAddr;
+
+async fn index() -> Html<String> {
+    render_html(None, None, None, None, None, None)
+}
+
+fn process_input_data(input_data: FormData) -> FormData {
+    match &input_data.random {
+        Some(random) if !random.is_empty() => input_data,
+        _ => FormData {
+            random: Some(nanoid!(12)),
+            ..input_data
+        },
+    }
+}
+
+async fn submit(Form(input_data): Form<FormData>) -> Html<String> {
+    let gameid = input_data.gameid.clone();
+    let fleetid = input_data.fleetid.clone();
+    let data = process_input_data(input_data);
+    let random = data.random.clone();
+    let mut board = data.board.clone();
+    let shots = data.shots.clone();
+    let response_text = match data.button.as_str() {
+        "Randomize" => match unmarshal_seed(&data) {
+            Ok(seed) => {
+                board = Some(random_placement(seed));
+                "OK".to_string()
+            }
+            Err(err) => err,
+        },
+        "Join" => join_game(data).await,
+        "Fire" => fire(data).await,
+        "Report" => report(data).await,
+        "ReportSalvo" => report_salvo(data).await,
+        "Wave" => wave(data).await,
+        "Win" => win(data).await,
+        "Resign" => resign(data).await,
+        "OfferDraw" => offer_draw(data).await,
+        "AcceptDraw" => accept_draw(data).await,
+        "Reveal" => reveal(data).await,
+        "Scan" => scan(data).await,
+        "AnswerScan" => answer_scan(data).await,
+        "Move" => {
+            // Show the ship where it moved to once the chain accepted the move
+            let moved = moved_board(&data);
+            let response = move_ship(data).await;
+            if let (Ok(moved), "OK") = (moved, response.as_str()) {
+                board = Some(moved.iter().map(|pos| pos.to_string()).collect::<Vec<String>>().join(","));
+            }
+            response
+        }
+        _ => "Unknown button pressed".to_string(),
+    };
+    render_html(gameid, fleetid, random, board, shots, Some(response_text))
+}
+
+// Lists the receipts saved by this host with links to download or submit them again
+async fn receipts_page() -> Html<String> {
+    let rows: String = list_receipts()
+        .iter()
+        .map(|name| {
+            format!(
+                "<li><a href=\"/receipts/{0}\">{0}</a> \
+                 <form action=\"/receipts/{0}/submit\" method=\"post\" style=\"display:inline\">\
+                 <button type=\"submit\">Submit</button></form></li>",
+                name
+            )
+        })
+        .collect();
+    Html(format!(
+        "<!DOCTYPE html><html><head><title>Saved Receipts</title></head><body>\
+         <h1>Saved Receipts</h1><ul>{}</ul><a href=\"/\">Back</a></body></html>",
+        rows
+    ))
+}
+
+async fn download_receipt(Path(name): Path<String>) -> impl IntoResponse {
+    match read_receipt(&name) {
+        Ok(json) => (
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)),
+            ],
+            json,
+        )
+            .into_response(),
+        Err(err) => (StatusCode::NOT_FOUND, err).into_response(),
+    }
+}
+
+async fn resubmit_receipt(Path(name): Path<String>) -> Html<String> {
+    let response_text = submit_saved_receipt(&name).await;
+    render_html(None, None, None, None, None, Some(response_text))
+}
+
+fn render_html(
+    gameid: Option<String>,
+    fleetid: Option<String>,
+    random: Option<String>,
+    board: Option<String>,
+    shots: Option<String>,
+    response: Option<String>,
+) -> Html<String> {
+    let fleetid = fleetid.unwrap_or("".to_string());
+    let gameid = gameid.unwrap_or("".to_string());
+    let response_html = if let Some(response) = response {
+        if response == "OK" {
+            if gameid != "" {
+                format!("Playing Game: <b>{}</b> with fleet's ID: <b>{}</b> ", gameid, fleetid)
+            } else {
+                "Not in game".to_string()
+            }
+        } else {
+            format!("<p style='color:red'>{}</p>", response)
+        }
+    } else {
+        "".to_string()
+    };
+    let random = random.unwrap_or("".to_string());
+
+    let board = board.unwrap_or("".to_string());
+    let shots = shots.unwrap_or("".to_string());
+
+    let path = "host/src/page.html";
+    let html = std::fs::read_to_string(path).unwrap();
+    let html = html.replace("{response_html}", &response_html);
+    let html = html.replace("{gameid}", &gameid);
+    let html = html.replace("{fleetid}", &fleetid);
+    let html = html.replace("{random}", &random);
+    let html = html.replace("{board}", &board);
+    let html = html.replace("{shots}", &shots);
+
+    // The maps fill in as the chain publishes the game's moves, a reload shows the latest
+    follow_game(&gameid);
+    let html = html.replace("{maps_html}", &shot_map_html(&gameid, &fleetid));
+
+    Html(html)
+}
+
+#[tokio::main]
+async fn main() {
+    // `host randomize [seed]` prints a valid fleet placement and exits
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("randomize") {
+        let seed = match args.get(2).map(|s| s.parse::<u64>()) {
+            None => None,
+            Some(Ok(seed)) => Some(seed),
+            Some(Err(_)) => {
+                eprintln!("Usage: host randomize [seed]");
+                std::process::exit(1);
+            }
+        };
+        println!("{}", random_placement(seed));
+        return;
+    }
+
+    // `host submit <file>` sends a receipt saved earlier, possibly on another machine
+    if args.get(1).map(String::as_str) == Some("submit") {
+        let Some(path) = args.get(2) else {
+            eprintln!("Usage: host submit <receipt file>");
+            std::process::exit(1);
+        };
+        match load_receipt_file(std::path::Path::new(path)) {
+            Ok(data) => println!("{}", submit_data(&data).await),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `host bot <game> <fleet> [difficulty] [instances] [seed]` plays without a browser
+    if args.get(1).map(String::as_str) == Some("bot") {
+        run_bots(&args[2..]).await;
+        return;
+    }
+
+    if let Err(err) = check_methods().await {
+        eprintln!("{}", err);
+    }
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/submit", post(submit))
+        .route("/receipts", get(receipts_page))
+        .route("/receipts/:name", get(download_receipt))
+        .route("/receipts/:name/submit", post(resubmit_receipt));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
+    println!("Listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+}
+
+async fn run_bots(args: &[String]) {
+    let usage = "Usage: host bot <game id> <fleet id> [easy|medium|hard] [instances] [seed] [classic|salvo|movable] [team]";
+    let (Some(gameid), Some(fleet)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+    let difficulty = match args.get(2).map(|d| d.parse::<Difficulty>()) {
+        None => Difficulty::Medium,
+        Some(Ok(difficulty)) => difficulty,
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let (Ok(instances), Ok(seed)) = (
+        args.get(3).map_or(Ok(1), |n| n.parse::<u64>()),
+        args.get(4).map(|s| s.parse::<u64>()).transpose(),
+    ) else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+    let mode = match args.get(5).map(|m| m.to_lowercase()).as_deref() {
+        None => None,
+        Some("classic") => Some(GameMode::Classic),
+        Some("salvo") => Some(GameMode::Salvo),
+        Some("movable") => Some(GameMode::Movable),
+        Some(_) => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    };
+
+    // Several instances share the game, each with its own fleet and seed
+    let mut bots = Vec::new();
+    for i in 0..instances {
+        let config = BotConfig {
+            gameid: gameid.clone(),
+            fleet: if instances == 1 { fleet.clone() } else { format!("{}{}", fleet, i + 1) },
+            difficulty,
+            seed: seed.map(|seed| seed + i),
+            mode,
+            team: args.get(6).cloned(),
+        };
+        let name = config.fleet.clone();
+        bots.push(tokio::spawn(async move {
+            if let Err(err) = run_bot(config).await {
+                eprintln!("[bot {}] {}", name, err);
+            }
+        }));
+    }
+    for bot in bots {
+        let _ = bot.await;
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,147 @@
+// src/receipts.rs
+
+use fleetcore::{
+    BaseJournal, BatchReportJournal, Command, CommunicationData, FireJournal, MoveJournal, OwnershipJournal, ReportJournal, ScanJournal,
+    ScanReportJournal,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Receipts saved by this process, it tells apart those saved in the same millisecond
+static SAVED: AtomicU64 = AtomicU64::new(0);
+
+// Folder where every proven move is kept until (and after) it reaches the chain.
+// Can be moved with the RECEIPTS_DIR environment variable.
+fn receipts_dir() -> PathBuf {
+    PathBuf::from(std::env::var("RECEIPTS_DIR").unwrap_or_else(|_| "receipts".to_string()))
+}
+
+// Only plain file names are accepted so a request can't read outside the receipts folder
+fn receipt_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.contains("..") {
+        return Err(format!("Invalid receipt name {}", name));
+    }
+    Ok(receipts_dir().join(name))
+}
+
+// Game and fleet the receipt is for, every journal starts with them
+fn owner(data: &CommunicationData) -> Option<(String, String)> {
+    let journal = &data.receipt.journal;
+    match data.cmd {
+        Command::Join | Command::Wave | Command::Win => journal.decode::<BaseJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::Resign | Command::OfferDraw | Command::AcceptDraw => {
+            journal.decode::<OwnershipJournal>().ok().map(|j| (j.gameid, j.fleet))
+        }
+        Command::Fire => journal.decode::<FireJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::Report => journal.decode::<ReportJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::BatchReport => journal.decode::<BatchReportJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::Scan => journal.decode::<ScanJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::ScanReport => journal.decode::<ScanReportJournal>().ok().map(|j| (j.gameid, j.fleet)),
+        Command::Move => journal.decode::<MoveJournal>().ok().map(|j| (j.gameid, j.fleet)),
+    }
+}
+
+// Keeps ids chosen by players safe to use in a file name
+fn file_safe(id: &str) -> String {
+    id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect()
+}
+
+// Writes the command and its receipt to a new file and returns the file name, e.g.
+// "1700000000000-0003-g1-alice-Report.json". The counter keeps receipts saved in the
+// same millisecond apart, and in the order they were saved.
+pub fn save_receipt(data: &CommunicationData) -> Result<String, String> {
+    let dir = receipts_dir();
+    std::fs::create_dir_all(&dir).map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let count = SAVED.fetch_add(1, Ordering::Relaxed) % 10_000;
+    let (gameid, fleet) = owner(data).unwrap_or_default();
+    let name = format!("{}-{:04}-{}-{}-{:?}.json", millis, count, file_safe(&gameid), file_safe(&fleet), data.cmd);
+
+    let json = serde_json::to_string(data).map_err(|err| format!("Could not encode receipt: {}", err))?;
+    // Never overwrite a proof, say one saved by another host sharing the folder
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dir.join(&name))
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .map_err(|err| format!("Could not save receipt {}: {}", name, err))?;
+    Ok(name)
+}
+
+// File next to a saved receipt that records the chain accepted it
+fn accepted_marker(name: &str) -> Result<PathBuf, String> {
+    Ok(receipt_path(name)?.with_extension("accepted"))
+}
+
+// Records that the chain accepted a saved receipt
+pub(crate) fn mark_accepted(name: &str) -> Result<(), String> {
+    let marker = accepted_marker(name)?;
+    std::fs::write(&marker, "").map_err(|err| format!("Could not write {}: {}", marker.display(), err))
+}
+
+// Whether the chain accepted the saved receipt, a refused one is no part of the game
+pub(crate) fn was_accepted(name: &str) -> bool {
+    accepted_marker(name).is_ok_and(|marker| marker.exists())
+}
+
+// File holding one kind of hidden cells of a fleet, e.g. "secrets/mines-g1-alice.json".
+// The ids are percent-encoded so two fleets never share a file.
+fn secret_path(kind: &str, gameid: &str, fleet: &str) -> PathBuf {
+    let id = |id: &str| utf8_percent_encode(id, NON_ALPHANUMERIC).to_string();
+    receipts_dir().join("secrets").join(format!("{}-{}-{}.json", kind, id(gameid), id(fleet)))
+}
+
+// Keeps cells the receipts only commit to, so a restarted host can still prove with them
+pub(crate) fn save_secret(kind: &str, gameid: &str, fleet: &str, cells: &[u8]) -> Result<(), String> {
+    let path = secret_path(kind, gameid, fleet);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+    }
+    let json = serde_json::to_string(cells).map_err(|err| format!("Could not encode {}: {}", kind, err))?;
+    std::fs::write(&path, json).map_err(|err| format!("Could not write {}: {}", path.display(), err))
+}
+
+// Cells saved with save_secret, None when this host never saved them
+pub(crate) fn load_secret(kind: &str, gameid: &str, fleet: &str) -> Option<Vec<u8>> {
+    let json = std::fs::read_to_string(secret_path(kind, gameid, fleet)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+// Reads back a receipt saved in the receipts folder
+pub fn load_receipt(name: &str) -> Result<CommunicationData, String> {
+    load_receipt_file(&receipt_path(name)?)
+}
+
+// Reads a receipt file from anywhere, e.g. one copied over from the proving machine
+pub fn load_receipt_file(path: &Path) -> Result<CommunicationData, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|err| format!("Could not read receipt {}: {}", path.display(), err))?;
+    serde_json::from_str(&json).map_err(|err| format!("Receipt {} is not valid: {}", path.display(), err))
+}
+
+// Raw contents of a saved receipt, as offered for download
+pub fn read_receipt(name: &str) -> Result<String, String> {
+    let path = receipt_path(name)?;
+    std::fs::read_to_string(&path).map_err(|err| format!("Could not read receipt {}: {}", name, err))
+}
+
+// Names of the saved receipts, oldest first
+pub fn list_receipts() -> Vec<String> {
+    let mut names: Vec<String> = match std::fs::read_dir(receipts_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names
+}
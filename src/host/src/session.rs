@@ -0,0 +1,245 @@
+// src/session.rs
+
+use fleetcore::BOARD_SIDE;
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+
+use crate::bot::{parse_event, ChainEvent};
+use crate::{chain_url, game_state};
+
+// Chain events of every game this host follows, in the order the chain published them
+static EVENTS: LazyLock<Mutex<HashMap<String, Vec<ChainEvent>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Games whose event stream is being read
+static FOLLOWING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+// Followed games played in Movable mode, where a ship can move away from its hits
+static MOVABLE: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+// Cells of a board, by position
+type Grid = [Mark; BOARD_SIDE * BOARD_SIDE];
+
+// What is known of one cell of a board
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    Unknown,
+    Miss,
+    Hit,
+    // Hit, and the ship it belongs to has no cell left to find
+    Sunk,
+    Mine,
+}
+
+// Shots of a game as one fleet sees them
+pub struct ShotMap {
+    // Our shots on every opponent's board, by fleet
+    pub opponents: BTreeMap<String, Grid>,
+    // Every shot on our own board
+    pub incoming: Grid,
+}
+
+// Reads the game's events from the chain in the background, once. The stream replays
+// the game from its start, so a host started mid game still sees all of it.
+pub fn follow_game(gameid: &str) {
+    if gameid.is_empty() || !FOLLOWING.lock().unwrap().insert(gameid.to_string()) {
+        return;
+    }
+    let gameid = gameid.to_string();
+    tokio::spawn(async move {
+        EVENTS.lock().unwrap().insert(gameid.clone(), Vec::new());
+        if let Err(err) = read_events(&gameid).await {
+            eprintln!("Stopped following game {}: {}", gameid, err);
+        }
+        // Followed again from the start by the next page that shows the game
+        FOLLOWING.lock().unwrap().remove(&gameid);
+    });
+}
+
+async fn read_events(gameid: &str) -> Result<(), String> {
+    let response = reqwest::get(format!("{}/games/{}/events", chain_url(), gameid))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("Could not follow game events: {}", err))?;
+    let mut stream = response.bytes_stream();
+
+    let mut buffer = String::new();
+    let mut mode_known = false;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| format!("Chain event stream failed: {}", err))?;
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find('\n') {
+            let line: String = buffer.drain(..=end).collect();
+            let Some(msg) = line.trim_end().strip_prefix("data:") else {
+                continue;
+            };
+            if let Some(event) = parse_event(msg.trim_start()) {
+                // The game exists once a fleet joined it, so its mode can be asked for
+                if !mode_known && matches!(event, ChainEvent::Joined { .. }) {
+                    if game_state(gameid).await?["mode"] == "Movable" {
+                        MOVABLE.lock().unwrap().insert(gameid.to_string());
+                    }
+                    mode_known = true;
+                }
+                EVENTS.lock().unwrap().entry(gameid.to_string()).or_default().push(event);
+            }
+        }
+    }
+    Err("Chain event stream closed".to_string())
+}
+
+// Plays the game's events so far from the fleet's side
+pub fn shot_map(gameid: &str, fleet: &str) -> ShotMap {
+    // A moved ship may have left cells next to its hits, so they prove nothing
+    let movable = MOVABLE.lock().unwrap().contains(gameid);
+    let events = EVENTS.lock().unwrap();
+    let mut map = ShotMap {
+        opponents: BTreeMap::new(),
+        incoming: [Mark::Unknown; BOARD_SIDE * BOARD_SIDE],
+    };
+    let mut teams: HashMap<&str, Option<&str>> = HashMap::new();
+    // Shooter, target and positions of the shots waiting for their report
+    let mut pending: Option<(&str, &str, &[u8])> = None;
+
+    for event in events.get(gameid).into_iter().flatten() {
+        match event {
+            ChainEvent::Joined { fleet: joined, team, .. } => {
+                teams.insert(joined, team.as_deref());
+                if joined != fleet {
+                    map.opponents.insert(joined.clone(), [Mark::Unknown; BOARD_SIDE * BOARD_SIDE]);
+                }
+            }
+            ChainEvent::Fired { fleet: shooter, target, positions } => {
+                pending = Some((shooter, target, positions));
+            }
+            ChainEvent::Reported { fleet: target, results, positions } => {
+                let Some((shooter, _, _)) = pending.filter(|(_, t, p)| *t == target.as_str() && *p == positions.as_slice()) else {
+                    continue;
+                };
+                pending = None;
+                let grid = if target == fleet {
+                    &mut map.incoming
+                } else if shooter == fleet {
+                    match map.opponents.get_mut(target) {
+                        Some(grid) => grid,
+                        None => continue,
+                    }
+                } else {
+                    continue;
+                };
+                for (&pos, result) in positions.iter().zip(results) {
+                    grid[pos as usize] = match result.as_str() {
+                        "Hit" => Mark::Hit,
+                        "Mine" => Mark::Mine,
+                        _ => Mark::Miss,
+                    };
+                }
+                if !movable {
+                    for &pos in positions {
+                        mark_sunk(grid, pos);
+                    }
+                }
+            }
+            ChainEvent::Eliminated { fleet: sunk, .. } => {
+                let grid = if sunk == fleet { Some(&mut map.incoming) } else { map.opponents.get_mut(sunk) };
+                for cell in grid.into_iter().flat_map(|grid| grid.iter_mut()) {
+                    if *cell == Mark::Hit {
+                        *cell = Mark::Sunk;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Teammates are not shot at, so they get no grid
+    let team = teams.get(fleet).copied().flatten();
+    if team.is_some() {
+        map.opponents.retain(|other, _| teams.get(other.as_str()).copied().flatten() != team);
+    }
+    map
+}
+
+// Ships never touch, so a line of hits whose every neighbour is known to be water
+// is a whole ship, and it is sunk
+fn mark_sunk(grid: &mut Grid, pos: u8) {
+    if grid[pos as usize] != Mark::Hit {
+        return;
+    }
+    let mut ship = vec![pos as usize];
+    let mut i = 0;
+    while i < ship.len() {
+        for next in neighbours(ship[i]) {
+            match grid[next] {
+                Mark::Hit | Mark::Sunk if !ship.contains(&next) => ship.push(next),
+                Mark::Unknown => return,
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    for cell in ship {
+        grid[cell] = Mark::Sunk;
+    }
+}
+
+fn neighbours(pos: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (pos % BOARD_SIDE, pos / BOARD_SIDE);
+    [
+        (x > 0).then(|| pos - 1),
+        (x + 1 < BOARD_SIDE).then(|| pos + 1),
+        (y > 0).then(|| pos - BOARD_SIDE),
+        (y + 1 < BOARD_SIDE).then(|| pos + BOARD_SIDE),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+// The fleet's grids as HTML, a click on an opponent's cell aims the next shot at it
+pub fn shot_map_html(gameid: &str, fleet: &str) -> String {
+    if gameid.is_empty() || fleet.is_empty() {
+        return String::new();
+    }
+    let map = shot_map(gameid, fleet);
+    let mut html = String::from("<div class=\"maps\">");
+    html.push_str(&grid_html("Shots on your fleet", None, &map.incoming));
+    for (opponent, grid) in &map.opponents {
+        html.push_str(&grid_html(&format!("Your shots at {}", opponent), Some(opponent), grid));
+    }
+    html.push_str("</div>");
+    html
+}
+
+fn grid_html(title: &str, target: Option<&str>, grid: &Grid) -> String {
+    let mut html = format!("<div><div class=\"map-title\">{}</div><div class=\"map\">", escape(title));
+    for (pos, mark) in grid.iter().enumerate() {
+        let class = match mark {
+            Mark::Unknown => "mark",
+            Mark::Miss => "mark mark-miss",
+            Mark::Hit => "mark mark-hit",
+            Mark::Sunk => "mark mark-sunk",
+            Mark::Mine => "mark mark-mine",
+        };
+        let xy = format!("{}{}", (b'A' + (pos % BOARD_SIDE) as u8) as char, pos / BOARD_SIDE);
+        match target {
+            Some(fleet) => html.push_str(&format!(
+                "<div class=\"{}\" title=\"{}\" data-fleet=\"{}\" data-pos=\"{}\" onclick=\"aim(this)\"></div>",
+                class,
+                xy,
+                escape(fleet),
+                xy
+            )),
+            None => html.push_str(&format!("<div class=\"{}\" title=\"{}\"></div>", class, xy)),
+        }
+    }
+    html.push_str("</div></div>");
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -1,3 +1,3 @@
-fn main() {
-    risc0_build::embed_methods();
-}
+fn main() {
+    risc0_build::embed_methods();
+}
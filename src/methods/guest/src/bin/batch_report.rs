@@ -0,0 +1,66 @@
+use fleetcore::{commitment, BatchReportInputs, BatchReportJournal, BoardHistory};
+use risc0_zkvm::guest::env;
+use risc0_zkvm::serde;
+
+fn main() {
+    // read the input
+    let input: BatchReportInputs = env::read();
+    let old_board_digest = commitment(&input.random, &input.board);
+
+    // Verify the previous receipt of this fleet and that it ends on the board we report on,
+    // so this receipt attests the whole history from the join commitment
+    let (origin, reports) = match &input.history {
+        BoardHistory::Joined(join) => {
+            env::verify(input.join_id, &serde::to_vec(join).unwrap()).unwrap();
+            assert_eq!(join.board, old_board_digest, "Board does not match the join commitment");
+            assert!(
+                join.gameid == input.gameid && join.fleet == input.fleet,
+                "Previous receipt belongs to another game or fleet"
+            );
+            (join.board, 1)
+        }
+        BoardHistory::BatchReported(previous) => {
+            env::verify(input.batch_report_id, &serde::to_vec(previous).unwrap()).unwrap();
+            assert_eq!(previous.next_board, old_board_digest, "Board does not match the previous report");
+            assert!(
+                previous.join_id == input.join_id && previous.batch_report_id == input.batch_report_id,
+                "Previous report was proven with other methods"
+            );
+            assert!(
+                previous.gameid == input.gameid && previous.fleet == input.fleet,
+                "Previous receipt belongs to another game or fleet"
+            );
+            (previous.origin, previous.reports + 1)
+        }
+        BoardHistory::Reported(_) => panic!("A batch report can't follow a single report"),
+        BoardHistory::Moved(_) => panic!("A batch report can't follow a move"),
+    };
+
+    // Every shot is reported as it fell, hit cells are removed from the board
+    let mut next_board = input.board.clone();
+    let mut results = Vec::new();
+    for (i, &pos) in input.positions.iter().enumerate() {
+        assert!(!input.positions[..i].contains(&pos), "Position {} reported twice", pos);
+        if let Some(index) = next_board.iter().position(|&p| p == pos) {
+            next_board.remove(index);
+            results.push("Hit".to_string());
+        } else {
+            results.push("Miss".to_string());
+        }
+    }
+
+    let output = BatchReportJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        positions: input.positions,
+        results,
+        board: old_board_digest,
+        next_board: commitment(&input.random, &next_board),
+        origin,
+        reports,
+        join_id: input.join_id,
+        batch_report_id: input.batch_report_id,
+    };
+
+    env::commit(&output);
+}
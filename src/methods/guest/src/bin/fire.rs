@@ -1,35 +1,52 @@
-use fleetcore::{FireInputs, FireJournal};
-use risc0_zkvm::guest::env;
-use risc0_zkvm::Digest;
-use sha2::{Digest as _, Sha256};
-
-fn main() {
-    // Read the input
-    let input: FireInputs = env::read();    // Extract the random value
-    let random = input.random.clone();
-
-    // Verify that at least one ship exists on the board
-    let _has_ships = !input.board.is_empty();
-
-    // Debug: Print the board state
-    eprintln!("Board state during fire: {:?}", input.board);
-
-    // Hash your board state for evidence
-    let mut hasher = Sha256::new();
-    hasher.update(random.as_bytes());
-    hasher.update(&input.board);
-    let hash_result = hasher.finalize();
-    let board_digest = Digest::try_from(hash_result.as_slice()).expect("Digest conversion failed");
-
-    // Fill the output journal with the required fields
-    let output = FireJournal {
-        gameid: input.gameid,
-        fleet: input.fleet,
-        board: board_digest,
-        target: input.target,
-        pos: input.pos,
-    };
-
-    // Commit the output to the journal
-    env::commit(&output);
-}
+use fleetcore::{commitment, on_board, surviving_ships, FireInputs, FireJournal};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // Read the input
+    let input: FireInputs = env::read();    // Extract the random value
+    let random = input.random.clone();
+
+    // Verify that at least one ship exists on the board
+    let _has_ships = !input.board.is_empty();
+
+    // Debug: Print the board state
+    eprintln!("Board state during fire: {:?}", input.board);
+
+    // Hash your board state for evidence
+    let board_digest = commitment(&random, &input.board);
+
+    // The current board must be what is left of the joined one, whose commitment the chain checks
+    assert!(
+        input.board.iter().all(|pos| input.origin_board.contains(pos)),
+        "Board is not part of the joined board"
+    );
+    assert!(input.origin_board.iter().all(|&pos| on_board(pos)), "Invalid position on the joined board");
+    let origin_digest = commitment(&random, &input.origin_board);
+
+    // At least one shot, and no more than the ships still afloat
+    let ships = surviving_ships(&input.origin_board, &input.board);
+    assert!(!input.positions.is_empty(), "No shot fired");
+    assert!(
+        input.positions.len() <= ships,
+        "Fired {} shots with only {} ships afloat",
+        input.positions.len(),
+        ships
+    );
+    for (i, &pos) in input.positions.iter().enumerate() {
+        assert!(on_board(pos), "Shot at position {} is off the board", pos);
+        assert!(!input.positions[..i].contains(&pos), "Fired twice at position {}", pos);
+    }
+
+    // Fill the output journal with the required fields
+    let output = FireJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: board_digest,
+        target: input.target,
+        positions: input.positions,
+        origin: origin_digest,
+    };
+
+    // Commit the output to the journal
+    env::commit(&output);
+}
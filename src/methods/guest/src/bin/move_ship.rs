@@ -0,0 +1,70 @@
+use fleetcore::{commitment, move_ship, BoardHistory, MoveInputs, MoveJournal};
+use risc0_zkvm::guest::env;
+use risc0_zkvm::serde;
+
+fn main() {
+    // read the input
+    let input: MoveInputs = env::read();
+    let old_board_digest = commitment(&input.random, &input.board);
+
+    // Verify the previous receipt of this fleet and that it ends on the board we move on,
+    // so this receipt carries the fleet's history on like a report does
+    let (origin, reports, gameid, fleet) = match &input.history {
+        BoardHistory::Joined(join) => {
+            env::verify(input.join_id, &serde::to_vec(join).unwrap()).unwrap();
+            assert_eq!(join.board, old_board_digest, "Board does not match the join commitment");
+            (join.board, 1, &join.gameid, &join.fleet)
+        }
+        BoardHistory::Reported(previous) => {
+            env::verify(input.report_id, &serde::to_vec(previous).unwrap()).unwrap();
+            assert_eq!(previous.next_board, old_board_digest, "Board does not match the previous report");
+            assert!(
+                previous.join_id == input.join_id
+                    && previous.report_id == input.report_id
+                    && previous.move_id == input.move_id,
+                "Previous report was proven with other methods"
+            );
+            (previous.origin, previous.reports + 1, &previous.gameid, &previous.fleet)
+        }
+        BoardHistory::Moved(previous) => {
+            env::verify(input.move_id, &serde::to_vec(previous).unwrap()).unwrap();
+            assert_eq!(previous.next_board, old_board_digest, "Board does not match the previous move");
+            assert!(
+                previous.join_id == input.join_id
+                    && previous.report_id == input.report_id
+                    && previous.move_id == input.move_id,
+                "Previous move was proven with other methods"
+            );
+            (previous.origin, previous.reports + 1, &previous.gameid, &previous.fleet)
+        }
+        BoardHistory::BatchReported(_) => panic!("Ships can't move in a salvo game"),
+    };
+    assert!(
+        *gameid == input.gameid && *fleet == input.fleet,
+        "Previous receipt belongs to another game or fleet"
+    );
+
+    // The moved ship has no hit, stays off the mines and the board still keeps to the
+    // spacing rule. The mines must be the ones committed at join, which the chain checks.
+    let mines_digest = (!input.mines.is_empty()).then(|| commitment(&input.random, &input.mines));
+    let next_board = match move_ship(&input.board, &input.hits, &input.mines, input.ship, input.direction) {
+        Ok(board) => board,
+        Err(reason) => panic!("{}", reason),
+    };
+
+    let output = MoveJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: old_board_digest,
+        next_board: commitment(&input.random, &next_board),
+        hits: input.hits,
+        mines: mines_digest,
+        origin,
+        reports,
+        join_id: input.join_id,
+        report_id: input.report_id,
+        move_id: input.move_id,
+    };
+
+    env::commit(&output);
+}
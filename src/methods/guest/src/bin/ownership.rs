@@ -0,0 +1,20 @@
+use fleetcore::{commitment, OwnershipInputs, OwnershipJournal};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // Read the input
+    let input: OwnershipInputs = env::read();
+
+    // Only the fleet knows the nonce that opens its board commitment, so the receipt
+    // proves the resignation or draw comes from the fleet itself
+    let output = OwnershipJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: commitment(&input.random, &input.board),
+        action: input.action,
+        offer: input.offer,
+    };
+
+    // Commit the output to the journal
+    env::commit(&output);
+}
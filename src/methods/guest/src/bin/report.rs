@@ -1,65 +1,116 @@
-use fleetcore::{ReportInputs, ReportJournal};
-use risc0_zkvm::guest::env;
-use risc0_zkvm::Digest;
-use sha2::{Digest as ShaDigest, Sha256};
-
-fn main() {
-    // read the input
-    let input: ReportInputs = env::read();
-
-    // Extract values that match game_actions.rs variable names
-    let board = input.board.clone();
-    let random = input.random.clone();
-    let pos = input.pos;
-
-    // In game_actions.rs, the report value would be passed separately
-    let _report = input.report.clone();
-
-    // Hash the current board
-    let mut hasher = Sha256::new();
-    hasher.update(random.as_bytes());
-    hasher.update(&board);
-    let hash_result = hasher.finalize();
-    let old_board_digest = Digest::try_from(hash_result.as_slice()).expect("Digest conversion failed");
-
-    // Check if the shot is a hit or miss based on board containing the position value
-    let is_hit = board.contains(&pos);
-    let report = if is_hit { "Hit".to_string() } else { "Miss".to_string() };    // Compare _report and report
-    if _report != report {
-        panic!("Provided report does not match actual result");
-    }
-
-    // Debug: Print board before modification
-    eprintln!("Board before hit processing: {:?}", board);
-
-    // Alter the board if it was a hit
-    let mut altered_board = board.clone();
-    if is_hit {
-        // Remove the hit position from the board
-        if let Some(pos_index) = altered_board.iter().position(|&p| p == pos) {
-            altered_board.remove(pos_index);
-        }
-    }
-
-    // Debug: Print board after modification
-    eprintln!("Board after hit processing: {:?}", altered_board);
-
-    // Hash the altered board
-    let mut hasher2 = Sha256::new();
-    hasher2.update(random.as_bytes());
-    hasher2.update(&altered_board);
-    let hash_result = hasher2.finalize();
-    let new_board_digest = Digest::try_from(hash_result.as_slice()).expect("Digest conversion failed");
-
-    // Fill the output journal with the required fields
-    let output = ReportJournal {
-        gameid: input.gameid,
-        fleet: input.fleet,
-        report,
-        pos: input.pos,
-        board: old_board_digest,
-        next_board: new_board_digest,
-    };
-
-    env::commit(&output);
-}
+use fleetcore::{commitment, BoardHistory, ReportInputs, ReportJournal};
+use risc0_zkvm::guest::env;
+use risc0_zkvm::serde;
+
+fn main() {
+    // read the input
+    let input: ReportInputs = env::read();
+
+    // Extract values that match game_actions.rs variable names
+    let board = input.board.clone();
+    let random = input.random.clone();
+    let pos = input.pos;
+
+    // In game_actions.rs, the report value would be passed separately
+    let _report = input.report.clone();
+
+    // Hash the current board
+    let old_board_digest = commitment(&random, &board);
+
+    // Verify the previous receipt of this fleet and that it ends on the board we report on,
+    // so this receipt attests the whole history from the join commitment
+    let (origin, reports) = match &input.history {
+        BoardHistory::Joined(join) => {
+            env::verify(input.join_id, &serde::to_vec(join).unwrap()).unwrap();
+            assert_eq!(join.board, old_board_digest, "Board does not match the join commitment");
+            (join.board, 1)
+        }
+        BoardHistory::Reported(previous) => {
+            env::verify(input.report_id, &serde::to_vec(previous).unwrap()).unwrap();
+            assert_eq!(previous.next_board, old_board_digest, "Board does not match the previous report");
+            // The previous report must itself have been checked against the same methods
+            assert!(
+                previous.join_id == input.join_id
+                    && previous.report_id == input.report_id
+                    && previous.move_id == input.move_id,
+                "Previous report was proven with other methods"
+            );
+            (previous.origin, previous.reports + 1)
+        }
+        BoardHistory::Moved(previous) => {
+            env::verify(input.move_id, &serde::to_vec(previous).unwrap()).unwrap();
+            assert_eq!(previous.next_board, old_board_digest, "Board does not match the previous move");
+            assert!(
+                previous.join_id == input.join_id
+                    && previous.report_id == input.report_id
+                    && previous.move_id == input.move_id,
+                "Previous move was proven with other methods"
+            );
+            (previous.origin, previous.reports + 1)
+        }
+        BoardHistory::BatchReported(_) => panic!("A single report can't follow a batch report"),
+    };
+    let (history_game, history_fleet) = match &input.history {
+        BoardHistory::Joined(join) => (&join.gameid, &join.fleet),
+        BoardHistory::Reported(previous) => (&previous.gameid, &previous.fleet),
+        BoardHistory::BatchReported(previous) => (&previous.gameid, &previous.fleet),
+        BoardHistory::Moved(previous) => (&previous.gameid, &previous.fleet),
+    };
+    assert!(
+        *history_game == input.gameid && *history_fleet == input.fleet,
+        "Previous receipt belongs to another game or fleet"
+    );
+
+    // The mines reported on must be the ones committed at join, which the chain checks
+    let mines_digest = (!input.mines.is_empty()).then(|| commitment(&random, &input.mines));
+
+    // Check if the shot is a hit or miss based on board containing the position value,
+    // mines are never on a ship so a shot in water may be on a mine
+    let is_hit = board.contains(&pos);
+    let report = if is_hit {
+        "Hit".to_string()
+    } else if input.mines.contains(&pos) {
+        "Mine".to_string()
+    } else {
+        "Miss".to_string()
+    };    // Compare _report and report
+    if _report != report {
+        panic!("Reported {} but the shot at position {} is a {}", _report, pos, report);
+    }
+
+    // Debug: Print board before modification
+    eprintln!("Board before hit processing: {:?}", board);
+
+    // Alter the board if it was a hit
+    let mut altered_board = board.clone();
+    if is_hit {
+        // Remove the hit position from the board
+        if let Some(pos_index) = altered_board.iter().position(|&p| p == pos) {
+            altered_board.remove(pos_index);
+        }
+    }
+
+    // Debug: Print board after modification
+    eprintln!("Board after hit processing: {:?}", altered_board);
+
+    // Hash the altered board
+    let new_board_digest = commitment(&random, &altered_board);
+
+    // Fill the output journal with the required fields
+    let output = ReportJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        report,
+        pos: input.pos,
+        board: old_board_digest,
+        next_board: new_board_digest,
+        mines: mines_digest,
+        origin,
+        reports,
+        join_id: input.join_id,
+        report_id: input.report_id,
+        move_id: input.move_id,
+    };
+
+    env::commit(&output);
+}
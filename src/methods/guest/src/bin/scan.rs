@@ -0,0 +1,24 @@
+use fleetcore::{commitment, ScanInputs, ScanJournal};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // Read the input
+    let input: ScanInputs = env::read();
+
+    // Hash the board of the scanning fleet, the chain checks it is its current one
+    let board_digest = commitment(&input.random, &input.board);
+
+    assert!(input.area.fits(), "Scanned area does not lie on the board");
+
+    // Fill the output journal with the required fields
+    let output = ScanJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: board_digest,
+        target: input.target,
+        area: input.area,
+    };
+
+    // Commit the output to the journal
+    env::commit(&output);
+}
@@ -0,0 +1,28 @@
+use fleetcore::{commitment, ScanReportInputs, ScanReportJournal};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // Read the input
+    let input: ScanReportInputs = env::read();
+
+    // Hash the board, the chain checks it is the fleet's current one so the count
+    // is taken on the committed board
+    let board_digest = commitment(&input.random, &input.board);
+
+    assert!(input.area.fits(), "Scanned area does not lie on the board");
+
+    // Only the number of ship cells in the area leaves the guest
+    let count = input.board.iter().filter(|&&pos| input.area.contains(pos)).count() as u8;
+
+    // Fill the output journal with the required fields
+    let output = ScanReportJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: board_digest,
+        area: input.area,
+        count,
+    };
+
+    // Commit the output to the journal
+    env::commit(&output);
+}
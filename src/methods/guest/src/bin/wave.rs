@@ -1,18 +1,18 @@
-use fleetcore::{BaseInputs, BaseJournal};
-use risc0_zkvm::guest::env;
-//use risc0_zkvm::Digest;
-//use sha2::{Digest as _, Sha256};
-
-fn main() {
-    // read the input
-    let input: BaseInputs = env::read();
-
-    // Fill the output journal with the required fields
-    let output = BaseJournal {
-        gameid: input.gameid,
-        fleet: input.fleet,
-        board: Default::default(), // Not needed for wave, but required by struct
-    };
-
-    env::commit(&output);
-}
+use fleetcore::{BaseInputs, BaseJournal};
+use risc0_zkvm::guest::env;
+
+fn main() {
+    // read the input
+    let input: BaseInputs = env::read();
+
+    // Fill the output journal with the required fields
+    let output = BaseJournal {
+        gameid: input.gameid,
+        fleet: input.fleet,
+        board: Default::default(), // Not needed for wave, but required by struct
+        team: None, // Declared at join only
+        mines: None, // Placed at join only
+    };
+
+    env::commit(&output);
+}